@@ -3,47 +3,87 @@ use std::borrow::{Borrow, BorrowMut};
 use std::mem::discriminant;
 use std::ops::{Deref, DerefMut};
 use js_sys::Float32Array;
+use serde_derive::Deserialize;
 use wasm_bindgen::prelude::*;
-use crate::bridge::{MidiSynthBridge, Synth};
-use crate::synth::{Envelope, WaveTableSynth};
-use crate::waves::{lerp_func, sawtooth_wave, sin_wave, square_wave, triangle_wave, wave_table_from_func};
+use dsp::bridge::{MidiSynthBridge, StealPolicy, Synth};
+use dsp::cc_map::{CcMapping, Curve};
+use dsp::events::{EventQueue, ScheduledEvent, SynthEvent, TimedEvent};
+use dsp::params::SynthParam;
+use dsp::envelope::{Envelope, EnvelopeStage};
+use dsp::synth::WaveTableSynth;
+use dsp::tuning::Tuning;
+use dsp::sytrus::SytrusParams;
 
-mod bridge;
-mod synth;
-mod waves;
 mod log;
-mod rand;
 
-static mut SYNTH: Option<MidiSynthBridge> = None;
+/// The volume envelope as the app sends it.
+#[derive(Deserialize)]
+struct EnvelopeSettings {
+    delay: f32,
+    attack: f32,
+    hold: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+    attack_curve: u8,
+    decay_curve: u8,
+    release_curve: u8,
+    /// -1 if the envelope does not loop
+    loop_start: i8,
+    loop_end: u8,
+}
+
+/// The number of samples rendered at once, matching the render quantum of an AudioWorklet.
+const BLOCK_SIZE: usize = 128;
+
+/// The sample rate used until the audio context reports its actual sample rate.
+const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+/// The maximum number of events that can be queued between two render calls.
+const EVENT_QUEUE_SIZE: usize = 256;
+
+/// How far ahead of the rendered audio an event may be scheduled, in seconds. Events with a later
+/// time are moved to this limit, so a bad timestamp cannot hold back an event for long.
+const MAX_SCHEDULE_AHEAD: f64 = 0.1;
+
+static mut SYNTH: Option<MidiSynthBridge<WaveTableSynth>> = None;
+
+/// Scratch buffers the synth renders the left and right channel into, so rendering never allocates.
+static mut LEFT_BUFFER: [f32; BLOCK_SIZE] = [0.0; BLOCK_SIZE];
+static mut RIGHT_BUFFER: [f32; BLOCK_SIZE] = [0.0; BLOCK_SIZE];
 
-fn create_synth() -> MidiSynthBridge {
+static mut EVENTS: Option<EventQueue> = None;
 
-    // create the wave function
-    let sin = Box::new(sin_wave);
-    let _square = Box::new(square_wave);
-    let _saw = Box::new(sawtooth_wave);
-    let _tri = Box::new(triangle_wave);
+/// The events of the block being rendered, taken from [EVENTS].
+static mut BLOCK_EVENTS: Option<Vec<TimedEvent>> = None;
 
-    let wave = sin; // lerp_func(sin, sin, 0.5);
+/// The frame of the audio context at the start of the last render call.
+static mut CURRENT_FRAME: u64 = 0;
+
+fn create_synth() -> MidiSynthBridge<WaveTableSynth> {
+
+    // start with the table the app's default Sytrus parameters generate, so the two agree until
+    // the app changes them
+    let wave_table = SytrusParams::default().wave_table(64);
 
     // create the synth
-    let wave_table = wave_table_from_func(wave, 64);
-    let sample_rate = 44100;
-    let envelope = Envelope::new(0.3, 0.4, 0.8, 0.5);
+    let sample_rate = DEFAULT_SAMPLE_RATE;
+    let envelope = Envelope::default();
     let synth = WaveTableSynth::new(sample_rate, wave_table, envelope);
 
     // create the bridge
-    let mut synth_bridge = MidiSynthBridge::new(Box::new(synth));
+    let mut synth_bridge = MidiSynthBridge::new(synth);
     synth_bridge.set_volume(1.0);
 
     return synth_bridge;
 }
 
-fn get_synth() -> &'static mut MidiSynthBridge {
+fn get_synth() -> &'static mut MidiSynthBridge<WaveTableSynth> {
     unsafe {
         match SYNTH {
             Some(ref mut synth) => synth,
             None => {
+                std::panic::set_hook(Box::new(console_error_panic_hook::hook));
                 SYNTH = Some(create_synth());
                 get_synth()
             }
@@ -51,30 +91,216 @@ fn get_synth() -> &'static mut MidiSynthBridge {
     }
 }
 
+fn get_events() -> &'static mut EventQueue {
+    unsafe {
+        match EVENTS {
+            Some(ref mut events) => events,
+            None => {
+                EVENTS = Some(EventQueue::new(EVENT_QUEUE_SIZE));
+                get_events()
+            }
+        }
+    }
+}
+
+fn get_block_events() -> &'static mut Vec<TimedEvent> {
+    unsafe {
+        match BLOCK_EVENTS {
+            Some(ref mut events) => events,
+            None => {
+                BLOCK_EVENTS = Some(Vec::with_capacity(EVENT_QUEUE_SIZE));
+                get_block_events()
+            }
+        }
+    }
+}
+
+/// Renders the next samples into `left` and `right`, the two channels of the worklet output.
+/// `current_frame` is the frame of the audio context at the first sample, the `currentFrame` of
+/// the worklet. Queued events are applied at their exact sample once they are due.
 #[wasm_bindgen]
-pub fn calculate_samples(len: u32) -> Float32Array {
+pub fn calculate_samples(left: &Float32Array, right: &Float32Array, current_frame: f64) {
+    let synth = get_synth();
+    let events = get_events();
+    let block_events = get_block_events();
+    let left_buffer = unsafe { &mut *std::ptr::addr_of_mut!(LEFT_BUFFER) };
+    let right_buffer = unsafe { &mut *std::ptr::addr_of_mut!(RIGHT_BUFFER) };
+
+    let current_frame = current_frame.max(0.0) as u64;
+    unsafe {
+        CURRENT_FRAME = current_frame;
+    }
+
+    let len = left.length().min(right.length()) as usize;
+    let mut start = 0;
+
+    while start < len {
+        let end = (start + BLOCK_SIZE).min(len);
+        let left_block = &mut left_buffer[..end - start];
+        let right_block = &mut right_buffer[..end - start];
+
+        events.take_block(current_frame + start as u64, left_block.len(), block_events);
+        synth.render(left_block, right_block, block_events);
+        block_events.clear();
+
+        if start == 0 && end == len {
+            left.copy_from(left_block);
+            right.copy_from(right_block);
+        } else {
+            left.subarray(start as u32, end as u32).copy_from(left_block);
+            right.subarray(start as u32, end as u32).copy_from(right_block);
+        }
+
+        start = end;
+    }
+}
+
+/// Queues an event to be applied at the given audio context time in seconds. Events with a time
+/// of 0 or in the past are applied at the start of the next block.
+fn queue_event(event: SynthEvent, time: f64) {
+    let sample_rate = get_synth().get_synth().sample_rate() as f64;
+    let current_frame = unsafe { CURRENT_FRAME };
+    let max_frame = current_frame + (MAX_SCHEDULE_AHEAD * sample_rate) as u64;
+    let frame = ((time * sample_rate).round().max(0.0) as u64).min(max_frame);
+
+    let scheduled = ScheduledEvent { frame, event };
+    if get_events().push(scheduled) {
+        return;
+    }
+
+    // the queue is full, so rather than dropping the event, the events due before it are applied
+    // right away to make room. If none are, every queued event is due later and applying this one
+    // right away still keeps them in order.
+    while let Some(due) = get_events().pop_due(frame) {
+        get_synth().on_event(due.event);
+    }
+    if !get_events().push(scheduled) {
+        get_synth().on_event(event);
+    }
+}
+
+#[wasm_bindgen]
+pub fn on_midi(is_active: bool, channel: u8, key: u8, velocity: u8, time: f64) {
     std::panic::set_hook(Box::new(console_error_panic_hook::hook));
 
-    let samples = Float32Array::new_with_length(len);
+    if is_active {
+        queue_event(SynthEvent::NoteOn { channel, key, velocity }, time);
+    } else {
+        queue_event(SynthEvent::NoteOff { channel, key }, time);
+    }
+}
+
+#[wasm_bindgen]
+pub fn on_pitch_bend(channel: u8, value: i16, time: f64) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+
+    queue_event(SynthEvent::PitchBend { channel, value }, time);
+}
+
+#[wasm_bindgen]
+pub fn on_control_change(channel: u8, controller: u8, value: u8, time: f64) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+
+    queue_event(SynthEvent::ControlChange { channel, controller, value }, time);
+}
+
+#[wasm_bindgen]
+pub fn set_polyphony(max_voices: u32, steal_policy: u8) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
 
     let synth = get_synth();
 
-    // populate samples with random values from -1 to 1
-    for i in 0..len {
-        let val = synth.get_sample();
-        samples.set_index(i, val);
+    synth.set_max_voices(max_voices as usize);
+    if let Some(steal_policy) = StealPolicy::from_u8(steal_policy) {
+        synth.set_steal_policy(steal_policy);
+    }
+}
+
+#[wasm_bindgen]
+pub fn set_param(param: u8, value: f32) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+
+    if let Some(param) = SynthParam::from_u8(param) {
+        get_synth().set_param(param, value);
     }
+}
+
+/// Sets the sample rate of the audio context, retuning all voices and envelopes.
+#[wasm_bindgen]
+pub fn set_sample_rate(sample_rate: f32) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
 
-    return samples;
+    log!("Setting sample rate to {}", sample_rate);
+    get_synth().set_sample_rate(sample_rate.round() as u32);
 }
 
+/// Replaces the volume envelope with the serialized [EnvelopeSettings]. Curves and stages are
+/// numbered like [Curve] and [EnvelopeStage], a negative loop start turns the loop off.
 #[wasm_bindgen]
-pub fn on_midi(is_active: bool, key: u8, velocity: u8) {
+pub fn set_envelope(settings: JsValue) {
     std::panic::set_hook(Box::new(console_error_panic_hook::hook));
 
-    let synth = get_synth();
+    let settings: EnvelopeSettings = match serde_wasm_bindgen::from_value(settings) {
+        Ok(settings) => settings,
+        Err(err) => {
+            log!("invalid envelope settings: {}", err);
+            return;
+        }
+    };
+
+    let mut envelope = Envelope::new(settings.attack.max(0.0), settings.decay.max(0.0),
+                                     settings.sustain.clamp(0.0, 1.0), settings.release.max(0.0));
+    envelope.delay = settings.delay.max(0.0);
+    envelope.hold = settings.hold.max(0.0);
+    envelope.attack_curve = Curve::from_u8(settings.attack_curve).unwrap_or(Curve::Linear);
+    envelope.decay_curve = Curve::from_u8(settings.decay_curve).unwrap_or(Curve::Linear);
+    envelope.release_curve = Curve::from_u8(settings.release_curve).unwrap_or(Curve::Linear);
+    envelope.loop_start = if settings.loop_start < 0 { None } else { EnvelopeStage::from_u8(settings.loop_start as u8) };
+    envelope.loop_end = EnvelopeStage::from_u8(settings.loop_end).unwrap_or(EnvelopeStage::Decay);
+
+    get_synth().get_synth().set_envelope(envelope);
+}
+
+/// Sets the points of the custom velocity curve, evenly spaced from velocity 0 to 127.
+#[wasm_bindgen]
+pub fn set_velocity_curve(points: Float32Array) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+
+    get_synth().get_synth().set_velocity_curve(&points.to_vec());
+}
+
+/// Sets the frequency in hz of every MIDI key, starting at key 0. Keys with a frequency of 0 are
+/// not played.
+#[wasm_bindgen]
+pub fn set_tuning(frequencies: Float32Array) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+
+    get_synth().set_tuning(Tuning::from_frequencies(&frequencies.to_vec()));
+}
+
+/// Routes a controller to a synth parameter. A negative channel listens to all channels.
+#[wasm_bindgen]
+pub fn set_cc_mapping(channel: i16, controller: u8, param: u8, min: f32, max: f32, curve: u8) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+
+    let (param, curve) = match (SynthParam::from_u8(param), Curve::from_u8(curve)) {
+        (Some(param), Some(curve)) => (param, curve),
+        _ => return,
+    };
+
+    let channel = if channel < 0 { None } else { Some(channel as u8) };
+    let mapping = CcMapping { channel, controller, param, min, max, curve };
+
+    if !get_synth().set_cc_mapping(mapping) {
+        log!("Too many controller mappings, ignoring controller {}", controller);
+    }
+}
+
+#[wasm_bindgen]
+pub fn remove_cc_mapping(controller: u8) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
 
-    synth.on_midi(is_active, key, velocity);
+    get_synth().remove_cc_mapping(controller);
 }
 
 trait AsAny {
@@ -87,10 +313,18 @@ impl<T: Any> AsAny for T {
     }
 }
 
+/// Replaces the wave table. A `frame_size` above 0 splits the table into frames of that many
+/// samples, which the wave table position moves between.
 #[wasm_bindgen]
-pub fn set_wave_table(wave_table: Float32Array) {
+pub fn set_wave_table(wave_table: Float32Array, frame_size: u32) {
     std::panic::set_hook(Box::new(console_error_panic_hook::hook));
 
+    if wave_table.length() == 0 {
+        log!("Ignoring an empty wave table");
+        return;
+    }
+
+    log!("Setting wave table");
     let synth = get_synth().get_synth();
-    synth.set_wave_table(Float32Array::to_vec(&wave_table));
+    synth.set_wave_table(Float32Array::to_vec(&wave_table), frame_size as usize);
 }
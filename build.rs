@@ -3,6 +3,8 @@ use std::process::Command;
 fn main() {
     // This tells Cargo to re-run the build script if the worker code changes.
     println!("cargo:rerun-if-changed=audio_worker/src/");
+    println!("cargo:rerun-if-changed=dsp/src/");
+    println!("cargo:rerun-if-changed=dsp/Cargo.toml");
     println!("cargo:rerun-if-changed=static/rust_audio_processor.js");
     println!("cargo:rerun-if-changed=static/text_decoder.js");
     println!("cargo:rerun-if-changed=audio_worker/Cargo.toml");
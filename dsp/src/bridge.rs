@@ -0,0 +1,942 @@
+use std::any::Any;
+use crate::cc_map::{CcMap, CcMapping};
+use crate::channel::{CC_SOFT, CC_SOSTENUTO, CC_SUSTAIN, ChannelState};
+use crate::events::{SynthEvent, TimedEvent};
+use crate::params::SynthParam;
+use crate::tuning::Tuning;
+use crate::unison::MAX_UNISON;
+
+/// The time in seconds over which a stolen voice is faded out.
+pub(crate) const STEAL_FADE_TIME: f32 = 0.005;
+
+/// The number of MIDI channels.
+const CHANNEL_COUNT: usize = 16;
+
+/// The default pitch bend range in semitones.
+pub const DEFAULT_PITCH_BEND_RANGE: f32 = 2.0;
+
+/// The factor applied to the velocity of notes played while the soft pedal is down.
+const SOFT_PEDAL_VELOCITY: f32 = 0.6;
+
+/// The highest number of voices that can be configured to sound at once.
+pub const MAX_VOICES: usize = 64;
+
+/// The number of voices that may sound at once until another limit is set.
+pub const DEFAULT_MAX_VOICES: usize = 32;
+
+/// The way a voice is stolen until another policy is set.
+pub const DEFAULT_STEAL_POLICY: StealPolicy = StealPolicy::SameKey;
+
+/// The number of preallocated voice slots. Every playing voice may need a second slot for the
+/// voice it stole while that one is still fading out.
+const VOICE_POOL_SIZE: usize = MAX_VOICES * 2;
+
+/// The number of held notes remembered in mono and legato mode. When more keys are held, the
+/// oldest one is forgotten.
+const NOTE_STACK_SIZE: usize = 128;
+
+#[derive(Clone, Copy, Default)]
+pub struct KeyState {
+    pub(crate) is_active: bool,
+    pub(crate) channel: u8,
+    pub(crate) key: u8,
+    /// the pitch of the key in the current tuning, in semitones where 69 is 440 hz
+    pub(crate) pitch: f32,
+    pub(crate) velocity: u8,
+    pub(crate) last_volume: f32,
+    pub(crate) start_volume: f32,
+    pub(crate) is_released: bool,
+    pub(crate) is_stolen: bool,
+    /// the key is up, but the voice is held by the sustain or sostenuto pedal
+    pub(crate) is_key_up: bool,
+    /// the key was down when the sostenuto pedal went down, so the voice is held until it lifts
+    pub(crate) is_sostenuto: bool,
+    pub(crate) time_since_pressed: f32,
+    pub(crate) time_since_released: f32,
+    pub(crate) time_since_stolen: f32,
+    /// the position within the current cycle of the wave of each unison oscillator, from 0 to 1
+    pub(crate) phases: [f32; MAX_UNISON],
+    /// the pitch offset in semitones, added to the key
+    pub(crate) pitch_offset: f32,
+    /// the position within the current cycle of the vibrato, from 0 to 1
+    pub(crate) vibrato_phase: f32,
+    /// the position within the current cycle of the wave table position lfo, from 0 to 1
+    pub(crate) position_lfo_phase: f32,
+    /// the distance in semitones to the pitch of the key, glides to 0
+    pub(crate) glide_offset: f32,
+    /// how fast the glide offset moves towards 0, in semitones per second
+    pub(crate) glide_rate: f32,
+    /// the position in the stereo field, from -1 for left to 1 for right
+    pub(crate) pan: f32,
+}
+
+impl KeyState {
+    fn new(channel: u8, key: u8, pitch: f32, velocity: u8) -> KeyState {
+        return KeyState {
+            is_active: true,
+            channel,
+            key,
+            pitch,
+            velocity,
+            ..KeyState::default()
+        };
+    }
+
+    fn release(&mut self) {
+        self.is_released = true;
+        self.is_key_up = false;
+        self.time_since_released = 0.0;
+        self.start_volume = self.last_volume; // ensure smooth transition when releasing quickly
+    }
+
+    /// The time in seconds since this voice was started.
+    fn age(&self) -> f32 {
+        return self.time_since_pressed + self.time_since_released;
+    }
+
+    /// Moves the glide offset towards 0 by the given time in seconds.
+    pub(crate) fn advance_glide(&mut self, dt: f32) {
+        let step = self.glide_rate * dt;
+        if self.glide_offset.abs() <= step {
+            self.glide_offset = 0.0;
+        } else {
+            self.glide_offset -= step * self.glide_offset.signum();
+        }
+    }
+}
+
+/// Returns the gains of the left and right channel for a pan from -1 to 1. The pan law keeps the
+/// power constant and is normalized so a centered sound plays at its full level on both channels.
+pub(crate) fn pan_gains(pan: f32) -> (f32, f32) {
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * std::f32::consts::FRAC_PI_4;
+    return (angle.cos() * std::f32::consts::SQRT_2, angle.sin() * std::f32::consts::SQRT_2);
+}
+
+/// Decides which voice is taken over when a note is played while all voices are in use.
+#[derive(Clone, Copy, PartialEq)]
+pub enum StealPolicy {
+    /// Steal the voice that was started first.
+    Oldest = 0,
+    /// Steal the voice with the lowest current volume.
+    Quietest = 1,
+    /// Retrigger a voice already playing the same key, otherwise steal the oldest.
+    SameKey = 2,
+    /// Steal the voice that has been released the longest, otherwise steal the oldest.
+    ReleasedFirst = 3,
+}
+
+impl StealPolicy {
+    pub const ALL: [StealPolicy; 4] = [
+        StealPolicy::Oldest,
+        StealPolicy::Quietest,
+        StealPolicy::SameKey,
+        StealPolicy::ReleasedFirst,
+    ];
+
+    pub fn from_u8(value: u8) -> Option<StealPolicy> {
+        return StealPolicy::ALL.get(value as usize).copied();
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            StealPolicy::Oldest => "Oldest",
+            StealPolicy::Quietest => "Quietest",
+            StealPolicy::SameKey => "Same Key",
+            StealPolicy::ReleasedFirst => "Released First",
+        };
+    }
+}
+
+/// How many notes can sound at once.
+#[derive(Clone, Copy, PartialEq)]
+pub enum VoiceMode {
+    /// Every note gets its own voice.
+    Poly = 0,
+    /// A single voice, every note restarts the envelope.
+    Mono = 1,
+    /// A single voice, notes played while another key is held only change the pitch.
+    Legato = 2,
+}
+
+impl VoiceMode {
+    pub const ALL: [VoiceMode; 3] = [VoiceMode::Poly, VoiceMode::Mono, VoiceMode::Legato];
+
+    pub fn from_u8(value: u8) -> Option<VoiceMode> {
+        return VoiceMode::ALL.get(value as usize).copied();
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            VoiceMode::Poly => "Poly",
+            VoiceMode::Mono => "Mono",
+            VoiceMode::Legato => "Legato",
+        };
+    }
+}
+
+/// Decides which of the held keys sounds in mono and legato mode.
+#[derive(Clone, Copy, PartialEq)]
+pub enum NotePriority {
+    /// The key pressed last.
+    Last = 0,
+    /// The lowest held key.
+    Low = 1,
+    /// The highest held key.
+    High = 2,
+}
+
+impl NotePriority {
+    pub const ALL: [NotePriority; 3] = [NotePriority::Last, NotePriority::Low, NotePriority::High];
+
+    pub fn from_u8(value: u8) -> Option<NotePriority> {
+        return NotePriority::ALL.get(value as usize).copied();
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            NotePriority::Last => "Last Note",
+            NotePriority::Low => "Low Note",
+            NotePriority::High => "High Note",
+        };
+    }
+}
+
+/// How the glide time is interpreted.
+#[derive(Clone, Copy, PartialEq)]
+pub enum GlideMode {
+    /// Every glide takes the glide time, no matter how far apart the notes are.
+    ConstantTime = 0,
+    /// The glide time is the time it takes to glide one octave.
+    ConstantRate = 1,
+}
+
+impl GlideMode {
+    pub const ALL: [GlideMode; 2] = [GlideMode::ConstantTime, GlideMode::ConstantRate];
+
+    pub fn from_u8(value: u8) -> Option<GlideMode> {
+        return GlideMode::ALL.get(value as usize).copied();
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            GlideMode::ConstantTime => "Constant Time",
+            GlideMode::ConstantRate => "Constant Rate",
+        };
+    }
+}
+
+/// A key that is held down in mono or legato mode.
+#[derive(Clone, Copy)]
+struct HeldNote {
+    channel: u8,
+    key: u8,
+    velocity: u8,
+}
+
+pub struct MidiSynthBridge<S: Synth> {
+    /// the preallocated voice pool, inactive voices are free to be reused
+    voices: Vec<KeyState>,
+    channels: [ChannelState; CHANNEL_COUNT],
+    synth: S,
+    volume: f32,
+    /// a second volume factor, usually controlled by the expression pedal
+    expression: f32,
+    /// routes controllers to parameters
+    cc_map: CcMap,
+    max_voices: usize,
+    steal_policy: StealPolicy,
+    voice_mode: VoiceMode,
+    note_priority: NotePriority,
+    /// the held keys in the order they were pressed, used in mono and legato mode
+    note_stack: Vec<HeldNote>,
+    /// the slot of the voice used in mono and legato mode
+    mono_voice: Option<usize>,
+    /// the glide time in seconds, 0 turns glide off
+    glide_time: f32,
+    glide_mode: GlideMode,
+    tuning: Tuning,
+    /// the pan of voices on middle C, from -1 to 1
+    pan: f32,
+    /// how far voices are panned by their key, from -1 to 1. At 1, the lowest key is panned fully
+    /// to the left and the highest fully to the right.
+    pan_spread: f32,
+    /// the balance of the output, from -1 to 1
+    balance: f32,
+}
+
+impl<S: Synth> MidiSynthBridge<S> {
+    pub fn new(synth: S) -> MidiSynthBridge<S> {
+        return MidiSynthBridge {
+            voices: vec![KeyState::default(); VOICE_POOL_SIZE],
+            channels: [ChannelState::new(DEFAULT_PITCH_BEND_RANGE); CHANNEL_COUNT],
+            synth,
+            volume: 1.0,
+            expression: 1.0,
+            cc_map: CcMap::new(),
+            max_voices: DEFAULT_MAX_VOICES,
+            steal_policy: DEFAULT_STEAL_POLICY,
+            voice_mode: VoiceMode::Poly,
+            note_priority: NotePriority::Last,
+            note_stack: Vec::with_capacity(NOTE_STACK_SIZE),
+            mono_voice: None,
+            glide_time: 0.0,
+            glide_mode: GlideMode::ConstantTime,
+            tuning: Tuning::equal_temperament(440.0),
+            pan: 0.0,
+            pan_spread: 0.0,
+            balance: 0.0,
+        };
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+    }
+
+    /// Sets a parameter of the bridge or, if the bridge does not handle it, of the synth.
+    pub fn set_param(&mut self, param: SynthParam, value: f32) {
+        match param {
+            SynthParam::Volume => self.set_volume(value),
+            SynthParam::Expression => self.expression = value,
+            SynthParam::PitchBendRange => {
+                for channel in 0..CHANNEL_COUNT {
+                    self.channels[channel].pitch_bend_range = value;
+                    self.update_pitch(channel as u8);
+                }
+            }
+            SynthParam::VoiceMode => {
+                if let Some(voice_mode) = VoiceMode::from_u8(value as u8) {
+                    self.set_voice_mode(voice_mode);
+                }
+            }
+            SynthParam::NotePriority => {
+                if let Some(note_priority) = NotePriority::from_u8(value as u8) {
+                    self.note_priority = note_priority;
+                }
+            }
+            SynthParam::GlideTime => self.glide_time = value.max(0.0),
+            SynthParam::Pan => self.pan = value.clamp(-1.0, 1.0),
+            SynthParam::PanSpread => self.pan_spread = value.clamp(-1.0, 1.0),
+            SynthParam::Balance => self.balance = value.clamp(-1.0, 1.0),
+            SynthParam::GlideMode => {
+                if let Some(glide_mode) = GlideMode::from_u8(value as u8) {
+                    self.glide_mode = glide_mode;
+                }
+            }
+            _ => self.synth.set_param(param, value),
+        }
+    }
+
+    /// Switches between poly, mono and legato mode. All playing voices are released, so no note
+    /// is left hanging in a mode that does not track it.
+    pub fn set_voice_mode(&mut self, voice_mode: VoiceMode) {
+        if voice_mode == self.voice_mode {
+            return;
+        }
+
+        for voice in self.voices.iter_mut() {
+            if voice.is_active && !voice.is_released {
+                voice.release();
+            }
+        }
+
+        self.voice_mode = voice_mode;
+        self.note_stack.clear();
+        self.mono_voice = None;
+    }
+
+    /// Sets the maximum number of voices that may sound at once, up to [MAX_VOICES]. Voices that
+    /// are fading out after being stolen do not count towards this limit. Every note costs as many
+    /// voices as the synth reports in [Synth::voice_cost], so with 4 unison oscillators a limit of
+    /// 32 voices plays 8 notes.
+    pub fn set_max_voices(&mut self, max_voices: usize) {
+        self.max_voices = max_voices.clamp(1, MAX_VOICES);
+    }
+
+    pub fn set_steal_policy(&mut self, steal_policy: StealPolicy) {
+        self.steal_policy = steal_policy;
+    }
+
+    /// Adds or replaces a controller mapping. Returns false if no more mappings fit.
+    pub fn set_cc_mapping(&mut self, mapping: CcMapping) -> bool {
+        return self.cc_map.set(mapping);
+    }
+
+    pub fn remove_cc_mapping(&mut self, controller: u8) {
+        self.cc_map.remove(controller);
+    }
+
+    /// Replaces the tuning. Playing voices are retuned right away, voices on keys the new tuning
+    /// does not map keep their pitch until they end.
+    pub fn set_tuning(&mut self, tuning: Tuning) {
+        for voice in self.voices.iter_mut() {
+            if let (true, Some(pitch)) = (voice.is_active, tuning.pitch(voice.key)) {
+                voice.pitch = pitch;
+            }
+        }
+
+        self.tuning = tuning;
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.synth.set_sample_rate(sample_rate);
+    }
+
+    pub fn get_synth(&mut self) -> &mut S {
+        return &mut self.synth;
+    }
+
+    /// Applies a single event immediately.
+    pub fn on_event(&mut self, event: SynthEvent) {
+        match event {
+            SynthEvent::NoteOn { channel, key, velocity } => self.on_midi(true, channel, key, velocity),
+            SynthEvent::NoteOff { channel, key } => self.on_midi(false, channel, key, 0),
+            SynthEvent::PitchBend { channel, value } => self.on_pitch_bend(channel, value),
+            SynthEvent::ControlChange { channel, controller, value } => self.on_control_change(channel, controller, value),
+        }
+    }
+
+    pub fn on_pitch_bend(&mut self, channel: u8, value: i16) {
+        let channel = channel as usize % CHANNEL_COUNT;
+        self.channels[channel].set_pitch_bend(value);
+        self.update_pitch(channel as u8);
+    }
+
+    pub fn on_control_change(&mut self, channel: u8, controller: u8, value: u8) {
+        let channel = channel as usize % CHANNEL_COUNT;
+        let is_down = value >= 64;
+
+        match controller {
+            CC_SUSTAIN => {
+                self.channels[channel].sustain = is_down;
+                if !is_down {
+                    self.release_held_voices(channel as u8);
+                }
+            }
+            CC_SOSTENUTO => {
+                if is_down == self.channels[channel].sostenuto {
+                    return;
+                }
+                self.channels[channel].sostenuto = is_down;
+
+                for voice in self.voices.iter_mut() {
+                    if voice.is_active && voice.channel == channel as u8 {
+                        // only keys that are down right now are held by the sostenuto pedal
+                        voice.is_sostenuto = is_down && !voice.is_released && !voice.is_key_up;
+                    }
+                }
+                if !is_down {
+                    self.release_held_voices(channel as u8);
+                }
+            }
+            CC_SOFT => self.channels[channel].soft = is_down,
+            _ => {
+                if self.channels[channel].on_control_change(controller, value) {
+                    self.update_pitch(channel as u8);
+                }
+            }
+        }
+
+        for i in 0..self.cc_map.len() {
+            let mapping = self.cc_map.get(i);
+            if mapping.matches(channel as u8, controller) {
+                self.set_param(mapping.param, mapping.value(value));
+            }
+        }
+    }
+
+    /// Releases the voices of a channel whose keys are up, unless a pedal still holds them.
+    fn release_held_voices(&mut self, channel: u8) {
+        let sustain = self.channels[channel as usize].sustain;
+
+        for voice in self.voices.iter_mut() {
+            if voice.is_active && voice.channel == channel && voice.is_key_up && !sustain && !voice.is_sostenuto {
+                voice.release();
+            }
+        }
+    }
+
+    /// Applies the pitch offset of a channel to all of its voices.
+    fn update_pitch(&mut self, channel: u8) {
+        let pitch_offset = self.channels[channel as usize].pitch_offset();
+        for voice in self.voices.iter_mut() {
+            if voice.is_active && voice.channel == channel {
+                voice.pitch_offset = pitch_offset;
+            }
+        }
+    }
+
+    pub fn on_midi(&mut self, pressed: bool, channel: u8, key: u8, velocity: u8) {
+        let channel = channel % CHANNEL_COUNT as u8;
+        let state = self.channels[channel as usize];
+
+        let velocity = if pressed && state.soft {
+            (velocity as f32 * SOFT_PEDAL_VELOCITY).round().max(1.0) as u8
+        } else {
+            velocity
+        };
+
+        // keys the tuning does not map stay silent
+        if pressed && self.tuning.pitch(key).is_none() {
+            return;
+        }
+
+        if self.voice_mode != VoiceMode::Poly {
+            if pressed {
+                self.on_mono_note_on(HeldNote { channel, key, velocity });
+            } else {
+                self.on_mono_note_off(channel, key);
+            }
+            return;
+        }
+
+        if !pressed {
+            for voice in self.voices.iter_mut() {
+                if voice.is_active && voice.channel == channel && voice.key == key && !voice.is_released && !voice.is_stolen {
+                    if state.sustain || voice.is_sostenuto {
+                        voice.is_key_up = true; // released once the pedals lift
+                    } else {
+                        voice.release();
+                    }
+                }
+            }
+            return;
+        }
+
+        if self.steal_policy == StealPolicy::SameKey {
+            let same_key = self.voices.iter_mut()
+                .find(|voice| voice.is_active && voice.channel == channel && voice.key == key && !voice.is_stolen);
+
+            if let Some(voice) = same_key {
+                voice.is_released = false;
+                voice.is_key_up = false;
+                voice.start_volume = voice.last_volume; // ensure smooth transition from last note
+                voice.time_since_pressed = 0.0;
+                voice.time_since_released = 0.0;
+                voice.velocity = velocity;
+                return;
+            }
+        }
+
+        let playing_voices = self.voices.iter()
+            .filter(|voice| voice.is_active && !voice.is_stolen)
+            .count();
+        let max_notes = (self.max_voices / self.synth.voice_cost().max(1)).max(1);
+
+        let mut victim = None;
+        if playing_voices >= max_notes {
+            victim = self.find_voice_to_steal();
+            if let Some(index) = victim {
+                let stolen = &mut self.voices[index];
+                stolen.is_stolen = true;
+                stolen.time_since_stolen = 0.0;
+            }
+        }
+
+        let index = self.find_free_voice().or(victim);
+        if let Some(index) = index {
+            let pitch = self.tuning.pitch(key).unwrap_or(key as f32);
+            self.voices[index] = KeyState::new(channel, key, pitch, velocity);
+            self.voices[index].pan = self.pan_for_key(key);
+            self.synth.start_voice(&mut self.voices[index]);
+            self.voices[index].pitch_offset = state.pitch_offset();
+        }
+    }
+
+    fn on_mono_note_on(&mut self, note: HeldNote) {
+        let was_held = !self.note_stack.is_empty();
+
+        self.note_stack.retain(|held| held.channel != note.channel || held.key != note.key);
+        if self.note_stack.len() == self.note_stack.capacity() {
+            self.note_stack.remove(0);
+        }
+        self.note_stack.push(note);
+
+        let sounding = self.mono_voice
+            .map(|index| self.voices[index])
+            .filter(|voice| voice.is_active && !voice.is_released && !voice.is_stolen);
+
+        let target = match self.priority_note() {
+            Some(target) => target,
+            None => return,
+        };
+
+        // with low or high priority, a key beyond the sounding one does not take over
+        if let Some(voice) = sounding {
+            let is_sounding = target.channel == voice.channel && target.key == voice.key;
+            let is_new = target.channel == note.channel && target.key == note.key;
+            if is_sounding && !is_new {
+                return;
+            }
+        }
+
+        let retrigger = self.voice_mode == VoiceMode::Mono || !was_held;
+        self.play_mono_note(target, retrigger);
+    }
+
+    fn on_mono_note_off(&mut self, channel: u8, key: u8) {
+        self.note_stack.retain(|held| held.channel != channel || held.key != key);
+
+        let index = match self.mono_voice {
+            Some(index) => index,
+            None => return,
+        };
+
+        let voice = self.voices[index];
+        if !voice.is_active || voice.is_released || voice.channel != channel || voice.key != key {
+            return;
+        }
+
+        // fall back to a key that is still held, like a monophonic keyboard does
+        if let Some(note) = self.priority_note() {
+            let retrigger = self.voice_mode == VoiceMode::Mono;
+            self.play_mono_note(note, retrigger);
+            return;
+        }
+
+        let state = self.channels[channel as usize];
+        let voice = &mut self.voices[index];
+        if state.sustain || voice.is_sostenuto {
+            voice.is_key_up = true; // released once the pedals lift
+        } else {
+            voice.release();
+        }
+    }
+
+    /// Returns the held key that should sound according to the note priority.
+    fn priority_note(&self) -> Option<HeldNote> {
+        return match self.note_priority {
+            NotePriority::Last => self.note_stack.last().copied(),
+            NotePriority::Low => self.note_stack.iter().min_by_key(|held| held.key).copied(),
+            NotePriority::High => self.note_stack.iter().max_by_key(|held| held.key).copied(),
+        };
+    }
+
+    /// Plays a note on the mono voice, gliding from the pitch it is at. If `retrigger` is false
+    /// and the voice is still sounding, only its pitch changes.
+    fn play_mono_note(&mut self, note: HeldNote, retrigger: bool) {
+        let pitch_offset = self.channels[note.channel as usize].pitch_offset();
+        let pitch = match self.tuning.pitch(note.key) {
+            Some(pitch) => pitch,
+            None => return,
+        };
+        let pan = self.pan_for_key(note.key);
+
+        let index = self.mono_voice
+            .filter(|index| self.voices[*index].is_active && !self.voices[*index].is_stolen)
+            .or_else(|| self.find_free_voice());
+        let index = match index {
+            Some(index) => index,
+            None => return,
+        };
+        self.mono_voice = Some(index);
+
+        let voice = &mut self.voices[index];
+
+        if !voice.is_active {
+            *voice = KeyState::new(note.channel, note.key, pitch, note.velocity);
+            voice.pitch_offset = pitch_offset;
+            voice.pan = pan;
+            self.synth.start_voice(voice);
+            return;
+        }
+
+        // glide from where the pitch is right now, even if the last glide did not finish
+        let glide_offset = voice.pitch + voice.glide_offset - pitch;
+        let glide_time = match self.glide_mode {
+            GlideMode::ConstantTime => self.glide_time,
+            GlideMode::ConstantRate => self.glide_time * glide_offset.abs() / 12.0,
+        };
+
+        if glide_time > 0.0 && glide_offset != 0.0 {
+            voice.glide_offset = glide_offset;
+            voice.glide_rate = glide_offset.abs() / glide_time;
+        } else {
+            voice.glide_offset = 0.0;
+        }
+
+        voice.channel = note.channel;
+        voice.key = note.key;
+        voice.pitch = pitch;
+        voice.pan = pan;
+        voice.pitch_offset = pitch_offset;
+        voice.is_key_up = false;
+
+        if retrigger || voice.is_released {
+            voice.is_released = false;
+            voice.start_volume = voice.last_volume; // ensure smooth transition from last note
+            voice.time_since_pressed = 0.0;
+            voice.time_since_released = 0.0;
+            voice.velocity = note.velocity;
+        }
+    }
+
+    /// Returns the pan of a new voice on the given key.
+    fn pan_for_key(&self, key: u8) -> f32 {
+        let key_offset = (key as f32 - 60.0) / 64.0;
+        return (self.pan + self.pan_spread * key_offset).clamp(-1.0, 1.0);
+    }
+
+    /// Returns the index of a voice slot that can be used for a new note. If every slot is in use,
+    /// the stolen voice that is closest to finishing its fade out is cut off.
+    fn find_free_voice(&self) -> Option<usize> {
+        let free = self.voices.iter().position(|voice| !voice.is_active);
+        if free.is_some() {
+            return free;
+        }
+
+        return self.voices.iter()
+            .enumerate()
+            .filter(|(_, voice)| voice.is_stolen)
+            .max_by(|(_, a), (_, b)| a.time_since_stolen.total_cmp(&b.time_since_stolen))
+            .map(|(i, _)| i);
+    }
+
+    /// Returns the index of the voice that should make room for a new note, according to the
+    /// current steal policy.
+    fn find_voice_to_steal(&self) -> Option<usize> {
+        let candidates = self.voices.iter()
+            .enumerate()
+            .filter(|(_, voice)| voice.is_active && !voice.is_stolen);
+
+        let oldest = candidates.clone()
+            .max_by(|(_, a), (_, b)| a.age().total_cmp(&b.age()))
+            .map(|(i, _)| i);
+
+        return match self.steal_policy {
+            StealPolicy::Oldest | StealPolicy::SameKey => oldest,
+            StealPolicy::Quietest => candidates
+                .min_by(|(_, a), (_, b)| a.last_volume.total_cmp(&b.last_volume))
+                .map(|(i, _)| i),
+            StealPolicy::ReleasedFirst => candidates
+                .filter(|(_, voice)| voice.is_released)
+                .max_by(|(_, a), (_, b)| a.time_since_released.total_cmp(&b.time_since_released))
+                .map(|(i, _)| i)
+                .or(oldest),
+        };
+    }
+
+    /// Renders the next block of samples into `left` and `right`, which have the same length. Each
+    /// event is applied at its sample offset within the block, events must be sorted by offset.
+    pub fn render(&mut self, left: &mut [f32], right: &mut [f32], events: &[TimedEvent]) {
+        left.fill(0.0);
+        right.fill(0.0);
+
+        let mut start = 0;
+        for event in events {
+            let offset = event.offset.clamp(start, left.len());
+            if offset > start {
+                self.synth.render(&mut self.voices, &mut left[start..offset], &mut right[start..offset]);
+                start = offset;
+            }
+            self.on_event(event.event);
+        }
+        self.synth.render(&mut self.voices, &mut left[start..], &mut right[start..]);
+
+        let gain = self.volume * self.expression;
+        let left_gain = gain * (1.0 - self.balance).min(1.0);
+        let right_gain = gain * (1.0 + self.balance).min(1.0);
+        for sample in left.iter_mut() {
+            *sample *= left_gain;
+        }
+        for sample in right.iter_mut() {
+            *sample *= right_gain;
+        }
+    }
+}
+
+pub trait Synth: Any {
+    fn sample_rate(&self) -> u32;
+
+    /// Changes the sample rate. Voice state is kept in seconds and cycles, so playing voices keep
+    /// their pitch and envelope position.
+    fn set_sample_rate(&mut self, sample_rate: u32);
+
+    /// Prepares a voice that is about to start playing a new note, like setting its start phases.
+    fn start_voice(&mut self, _voice: &mut KeyState) {
+    }
+
+    /// Returns how many voices of the polyphony budget a single note uses.
+    fn voice_cost(&self) -> usize {
+        return 1;
+    }
+
+    /// Adds the next `left.len()` samples of a single voice to the left and right channel, panned
+    /// by the pan of the voice, and advances the voice state. Returns false once the voice has
+    /// finished sounding.
+    fn render_voice(&mut self, voice: &mut KeyState, left: &mut [f32], right: &mut [f32]) -> bool;
+
+    /// Adds the next `left.len()` samples of all active voices to the left and right channel.
+    /// Engines can override this to process all voices in one inner loop instead of one voice at a
+    /// time.
+    fn render(&mut self, voices: &mut [KeyState], left: &mut [f32], right: &mut [f32]) {
+        for voice in voices.iter_mut() {
+            if !voice.is_active {
+                continue;
+            }
+            voice.is_active = self.render_voice(voice, left, right);
+        }
+    }
+
+    fn set_param(&mut self, param: SynthParam, value: f32);
+
+    /// Replaces the wave table. `frame_size` splits it into frames of that many samples, 0 uses the
+    /// whole table as a single frame. An empty table is ignored.
+    fn set_wave_table(&mut self, wave_table: Vec<f32>, frame_size: usize);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A synth that does not render anything, with a configurable cost per note.
+    struct TestSynth {
+        voice_cost: usize,
+    }
+
+    impl Synth for TestSynth {
+        fn sample_rate(&self) -> u32 {
+            return 44100;
+        }
+
+        fn set_sample_rate(&mut self, _sample_rate: u32) {
+        }
+
+        fn voice_cost(&self) -> usize {
+            return self.voice_cost;
+        }
+
+        fn render_voice(&mut self, _voice: &mut KeyState, _left: &mut [f32], _right: &mut [f32]) -> bool {
+            return true;
+        }
+
+        fn set_param(&mut self, _param: SynthParam, _value: f32) {
+        }
+
+        fn set_wave_table(&mut self, _wave_table: Vec<f32>, _frame_size: usize) {
+        }
+    }
+
+    fn bridge(max_voices: usize, steal_policy: StealPolicy, voice_cost: usize) -> MidiSynthBridge<TestSynth> {
+        let mut bridge = MidiSynthBridge::new(TestSynth { voice_cost });
+        bridge.set_max_voices(max_voices);
+        bridge.set_steal_policy(steal_policy);
+        return bridge;
+    }
+
+    fn voice_mut(bridge: &mut MidiSynthBridge<TestSynth>, key: u8) -> &mut KeyState {
+        return bridge.voices.iter_mut()
+            .find(|voice| voice.is_active && !voice.is_stolen && voice.key == key)
+            .unwrap();
+    }
+
+    /// The keys of the voices that are playing and not being stolen, in ascending order.
+    fn playing_keys(bridge: &MidiSynthBridge<TestSynth>) -> Vec<u8> {
+        let mut keys = bridge.voices.iter()
+            .filter(|voice| voice.is_active && !voice.is_stolen)
+            .map(|voice| voice.key)
+            .collect::<Vec<u8>>();
+        keys.sort();
+        return keys;
+    }
+
+    #[test]
+    fn oldest_steals_the_voice_started_first() {
+        let mut bridge = bridge(2, StealPolicy::Oldest, 1);
+        bridge.on_midi(true, 0, 60, 100);
+        bridge.on_midi(true, 0, 62, 100);
+        voice_mut(&mut bridge, 60).time_since_pressed = 2.0;
+        voice_mut(&mut bridge, 62).time_since_pressed = 1.0;
+
+        bridge.on_midi(true, 0, 64, 100);
+
+        assert_eq!(playing_keys(&bridge), vec![62, 64]);
+    }
+
+    #[test]
+    fn quietest_steals_the_voice_with_the_lowest_volume() {
+        let mut bridge = bridge(2, StealPolicy::Quietest, 1);
+        bridge.on_midi(true, 0, 60, 100);
+        bridge.on_midi(true, 0, 62, 100);
+        voice_mut(&mut bridge, 60).last_volume = 0.9;
+        voice_mut(&mut bridge, 62).last_volume = 0.1;
+
+        bridge.on_midi(true, 0, 64, 100);
+
+        assert_eq!(playing_keys(&bridge), vec![60, 64]);
+    }
+
+    #[test]
+    fn same_key_retriggers_the_voice_of_the_key() {
+        let mut bridge = bridge(4, StealPolicy::SameKey, 1);
+        bridge.on_midi(true, 0, 60, 100);
+        voice_mut(&mut bridge, 60).time_since_pressed = 1.0;
+        bridge.on_midi(false, 0, 60, 0);
+
+        bridge.on_midi(true, 0, 60, 80);
+
+        assert_eq!(playing_keys(&bridge), vec![60]);
+        let voice = voice_mut(&mut bridge, 60);
+        assert!(!voice.is_released);
+        assert_eq!(voice.time_since_pressed, 0.0);
+        assert_eq!(voice.velocity, 80);
+    }
+
+    #[test]
+    fn same_key_steals_the_oldest_voice_for_other_keys() {
+        let mut bridge = bridge(2, StealPolicy::SameKey, 1);
+        bridge.on_midi(true, 0, 60, 100);
+        bridge.on_midi(true, 0, 62, 100);
+        voice_mut(&mut bridge, 60).time_since_pressed = 1.0;
+        voice_mut(&mut bridge, 62).time_since_pressed = 2.0;
+
+        bridge.on_midi(true, 0, 64, 100);
+
+        assert_eq!(playing_keys(&bridge), vec![60, 64]);
+    }
+
+    #[test]
+    fn released_first_prefers_released_voices() {
+        let mut bridge = bridge(2, StealPolicy::ReleasedFirst, 1);
+        bridge.on_midi(true, 0, 60, 100);
+        bridge.on_midi(true, 0, 62, 100);
+        voice_mut(&mut bridge, 60).time_since_pressed = 2.0;
+        voice_mut(&mut bridge, 62).time_since_pressed = 1.0;
+        bridge.on_midi(false, 0, 62, 0);
+
+        bridge.on_midi(true, 0, 64, 100);
+
+        assert_eq!(playing_keys(&bridge), vec![60, 64]);
+    }
+
+    #[test]
+    fn released_first_falls_back_to_the_oldest_voice() {
+        let mut bridge = bridge(2, StealPolicy::ReleasedFirst, 1);
+        bridge.on_midi(true, 0, 60, 100);
+        bridge.on_midi(true, 0, 62, 100);
+        voice_mut(&mut bridge, 60).time_since_pressed = 1.0;
+        voice_mut(&mut bridge, 62).time_since_pressed = 2.0;
+
+        bridge.on_midi(true, 0, 64, 100);
+
+        assert_eq!(playing_keys(&bridge), vec![60, 64]);
+    }
+
+    #[test]
+    fn notes_are_limited_by_the_voice_cost() {
+        let mut bridge = bridge(8, StealPolicy::Oldest, 4);
+        for key in [60, 62, 64] {
+            bridge.on_midi(true, 0, key, 100);
+        }
+
+        assert_eq!(playing_keys(&bridge).len(), 2);
+    }
+
+    #[test]
+    fn one_note_plays_even_if_it_costs_more_than_the_limit() {
+        let mut bridge = bridge(2, StealPolicy::Oldest, 16);
+        bridge.on_midi(true, 0, 60, 100);
+        bridge.on_midi(true, 0, 62, 100);
+
+        assert_eq!(playing_keys(&bridge), vec![62]);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::params::SynthParam;
+
+/// The number of mappings that can be stored, reserved up front so changing mappings while
+/// playing does not allocate.
+const MAX_MAPPINGS: usize = 128;
+
+/// How a controller value is shaped before it is scaled to the range of a mapping.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Curve {
+    Linear = 0,
+    /// slow at first, fast towards the end, good for volumes and times
+    Exponential = 1,
+    /// fast at first, slow towards the end
+    Logarithmic = 2,
+    /// slow at both ends, fast in the middle
+    SCurve = 3,
+}
+
+impl Curve {
+    pub const ALL: [Curve; 4] = [Curve::Linear, Curve::Exponential, Curve::Logarithmic, Curve::SCurve];
+
+    pub fn from_u8(value: u8) -> Option<Curve> {
+        return Curve::ALL.get(value as usize).copied();
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Curve::Linear => "Linear",
+            Curve::Exponential => "Exponential",
+            Curve::Logarithmic => "Logarithmic",
+            Curve::SCurve => "S-Curve",
+        };
+    }
+
+    /// Shapes x, from 0 to 1, keeping both ends in place.
+    pub fn apply(&self, x: f32) -> f32 {
+        const STEEPNESS: f32 = 4.0;
+
+        return match self {
+            Curve::Linear => x,
+            Curve::Exponential => ((STEEPNESS * x).exp() - 1.0) / (STEEPNESS.exp() - 1.0),
+            Curve::Logarithmic => (1.0 + (STEEPNESS.exp() - 1.0) * x).ln() / STEEPNESS,
+            Curve::SCurve => x * x * (3.0 - 2.0 * x),
+        };
+    }
+}
+
+/// Routes a MIDI controller to a synth parameter.
+#[derive(Clone, Copy, PartialEq)]
+pub struct CcMapping {
+    /// the channel the mapping listens to, or None for all channels
+    pub channel: Option<u8>,
+    pub controller: u8,
+    pub param: SynthParam,
+    /// the parameter value when the controller is at 0
+    pub min: f32,
+    /// the parameter value when the controller is at 127
+    pub max: f32,
+    pub curve: Curve,
+}
+
+impl CcMapping {
+    /// Returns the parameter value for the given controller value.
+    pub fn value(&self, controller_value: u8) -> f32 {
+        let x = self.curve.apply(controller_value.min(127) as f32 / 127.0);
+        return self.min + (self.max - self.min) * x;
+    }
+
+    pub fn matches(&self, channel: u8, controller: u8) -> bool {
+        return self.controller == controller && self.channel.is_none_or(|c| c == channel);
+    }
+}
+
+/// The table of all controller mappings.
+pub struct CcMap {
+    mappings: Vec<CcMapping>,
+}
+
+impl Default for CcMap {
+    fn default() -> CcMap {
+        return CcMap::new();
+    }
+}
+
+impl CcMap {
+    /// Creates the default mappings: mod wheel to vibrato depth, channel volume to volume and
+    /// expression to expression.
+    pub fn new() -> CcMap {
+        let mut mappings = Vec::with_capacity(MAX_MAPPINGS);
+
+        mappings.push(CcMapping { channel: None, controller: 1, param: SynthParam::VibratoDepth, min: 0.0, max: 1.0, curve: Curve::Linear });
+        mappings.push(CcMapping { channel: None, controller: 7, param: SynthParam::Volume, min: 0.0, max: 1.0, curve: Curve::Exponential });
+        mappings.push(CcMapping { channel: None, controller: 11, param: SynthParam::Expression, min: 0.0, max: 1.0, curve: Curve::Exponential });
+
+        return CcMap {
+            mappings,
+        };
+    }
+
+    /// Adds a mapping, replacing the one with the same channel, controller and parameter.
+    /// Returns false if the table is full.
+    pub fn set(&mut self, mapping: CcMapping) -> bool {
+        let existing = self.mappings.iter_mut().find(|m| {
+            m.channel == mapping.channel && m.controller == mapping.controller && m.param == mapping.param
+        });
+
+        if let Some(existing) = existing {
+            *existing = mapping;
+            return true;
+        }
+
+        if self.mappings.len() == self.mappings.capacity() {
+            return false;
+        }
+
+        self.mappings.push(mapping);
+        return true;
+    }
+
+    /// Removes all mappings of a controller.
+    pub fn remove(&mut self, controller: u8) {
+        self.mappings.retain(|m| m.controller != controller);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.mappings.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.mappings.is_empty();
+    }
+
+    pub fn get(&self, index: usize) -> CcMapping {
+        return self.mappings[index];
+    }
+}
@@ -0,0 +1,81 @@
+/// The controller numbers used to select a registered parameter and set its value.
+const CC_DATA_ENTRY_MSB: u8 = 6;
+const CC_DATA_ENTRY_LSB: u8 = 38;
+const CC_NRPN_LSB: u8 = 98;
+const CC_NRPN_MSB: u8 = 99;
+const CC_RPN_LSB: u8 = 100;
+const CC_RPN_MSB: u8 = 101;
+
+/// The controller numbers of the piano pedals. Values of 64 and above mean the pedal is down.
+pub(crate) const CC_SUSTAIN: u8 = 64;
+pub(crate) const CC_SOSTENUTO: u8 = 66;
+pub(crate) const CC_SOFT: u8 = 67;
+
+/// The registered parameter number of the pitch bend range.
+const RPN_PITCH_BEND_RANGE: (u8, u8) = (0, 0);
+
+/// Selecting this registered parameter number disables data entry.
+const RPN_NULL: (u8, u8) = (127, 127);
+
+/// The state of a single MIDI channel.
+#[derive(Clone, Copy)]
+pub struct ChannelState {
+    /// the position of the pitch bend wheel, from -1 to 1
+    pub(crate) pitch_bend: f32,
+    /// how many semitones the pitch bend wheel bends at its extremes
+    pub(crate) pitch_bend_range: f32,
+    /// whether the sustain pedal is down, which defers all note offs
+    pub(crate) sustain: bool,
+    /// whether the sostenuto pedal is down, which defers note offs of the keys held when it went down
+    pub(crate) sostenuto: bool,
+    /// whether the soft pedal is down, which makes new notes softer
+    pub(crate) soft: bool,
+    /// the selected registered parameter number (msb, lsb)
+    rpn: (u8, u8),
+}
+
+impl ChannelState {
+    pub fn new(pitch_bend_range: f32) -> ChannelState {
+        return ChannelState {
+            pitch_bend: 0.0,
+            pitch_bend_range,
+            sustain: false,
+            sostenuto: false,
+            soft: false,
+            rpn: RPN_NULL,
+        };
+    }
+
+    /// The current pitch offset of this channel in semitones.
+    pub fn pitch_offset(&self) -> f32 {
+        return self.pitch_bend * self.pitch_bend_range;
+    }
+
+    /// Sets the pitch bend from a 14 bit MIDI value, where 0 is the center.
+    pub fn set_pitch_bend(&mut self, value: i16) {
+        self.pitch_bend = (value as f32 / 8192.0).clamp(-1.0, 1.0);
+    }
+
+    /// Handles the registered parameter controllers. Returns true if the pitch of the channel changed.
+    pub fn on_control_change(&mut self, controller: u8, value: u8) -> bool {
+        match controller {
+            CC_RPN_MSB => self.rpn.0 = value,
+            CC_RPN_LSB => self.rpn.1 = value,
+            // non-registered parameters are not supported, but must not change registered ones
+            CC_NRPN_MSB | CC_NRPN_LSB => self.rpn = RPN_NULL,
+            CC_DATA_ENTRY_MSB if self.rpn == RPN_PITCH_BEND_RANGE => {
+                let cents = self.pitch_bend_range.fract();
+                self.pitch_bend_range = value as f32 + cents;
+                return true;
+            }
+            CC_DATA_ENTRY_LSB if self.rpn == RPN_PITCH_BEND_RANGE => {
+                let semitones = self.pitch_bend_range.trunc();
+                self.pitch_bend_range = semitones + value.min(99) as f32 / 100.0;
+                return true;
+            }
+            _ => {}
+        }
+
+        return false;
+    }
+}
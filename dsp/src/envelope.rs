@@ -0,0 +1,297 @@
+use crate::bridge::{KeyState, STEAL_FADE_TIME};
+use crate::cc_map::Curve;
+use crate::params::SynthParam;
+
+/// A stage of the envelope before the sustain level is reached.
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum EnvelopeStage {
+    Delay = 0,
+    Attack = 1,
+    Hold = 2,
+    Decay = 3,
+}
+
+impl EnvelopeStage {
+    pub const ALL: [EnvelopeStage; 4] = [
+        EnvelopeStage::Delay,
+        EnvelopeStage::Attack,
+        EnvelopeStage::Hold,
+        EnvelopeStage::Decay,
+    ];
+
+    pub fn from_u8(value: u8) -> Option<EnvelopeStage> {
+        return EnvelopeStage::ALL.get(value as usize).copied();
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            EnvelopeStage::Delay => "Delay",
+            EnvelopeStage::Attack => "Attack",
+            EnvelopeStage::Hold => "Hold",
+            EnvelopeStage::Decay => "Decay",
+        };
+    }
+}
+
+/// A delay, attack, hold, decay, sustain, release envelope. Each moving segment has its own
+/// curve, and a range of stages can be looped while the key is held.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Envelope {
+    /// the time in seconds before the attack starts
+    pub delay: f32,
+    pub attack: f32,
+    /// the time in seconds the envelope stays at its peak after the attack
+    pub hold: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+    pub attack_curve: Curve,
+    pub decay_curve: Curve,
+    pub release_curve: Curve,
+    /// the first stage of the loop, None if the envelope does not loop
+    pub loop_start: Option<EnvelopeStage>,
+    /// the last stage of the loop, once it ends the envelope jumps back to the start of the loop
+    pub loop_end: EnvelopeStage,
+}
+
+impl Default for Envelope {
+    /// The envelope the synth starts with.
+    fn default() -> Envelope {
+        return Envelope::new(0.3, 0.4, 0.8, 0.5);
+    }
+}
+
+impl Envelope {
+    /// Creates a linear ADSR envelope without delay, hold or loop.
+    pub fn new(attack: f32, decay: f32, sustain: f32, release: f32) -> Envelope {
+        return Envelope {
+            delay: 0.0,
+            attack,
+            hold: 0.0,
+            decay,
+            sustain,
+            release,
+            attack_curve: Curve::Linear,
+            decay_curve: Curve::Linear,
+            release_curve: Curve::Linear,
+            loop_start: None,
+            loop_end: EnvelopeStage::Decay,
+        };
+    }
+
+    /// Sets an envelope parameter. Returns false if the parameter does not belong to the envelope.
+    pub fn set_param(&mut self, param: SynthParam, value: f32) -> bool {
+        match param {
+            SynthParam::Attack => self.attack = value.max(0.0),
+            SynthParam::Decay => self.decay = value.max(0.0),
+            SynthParam::Sustain => self.sustain = value.clamp(0.0, 1.0),
+            SynthParam::Release => self.release = value.max(0.0),
+            SynthParam::Delay => self.delay = value.max(0.0),
+            SynthParam::Hold => self.hold = value.max(0.0),
+            SynthParam::AttackCurve => self.attack_curve = Curve::from_u8(value as u8).unwrap_or(self.attack_curve),
+            SynthParam::DecayCurve => self.decay_curve = Curve::from_u8(value as u8).unwrap_or(self.decay_curve),
+            SynthParam::ReleaseCurve => self.release_curve = Curve::from_u8(value as u8).unwrap_or(self.release_curve),
+            // a negative value turns the loop off
+            SynthParam::EnvelopeLoopStart => self.loop_start = if value < 0.0 { None } else { EnvelopeStage::from_u8(value as u8) },
+            SynthParam::EnvelopeLoopEnd => self.loop_end = EnvelopeStage::from_u8(value as u8).unwrap_or(self.loop_end),
+            _ => return false,
+        }
+        return true;
+    }
+
+    /// Returns a copy of the envelope with the attack time scaled by the given factor.
+    pub fn with_attack_scaled(&self, factor: f32) -> Envelope {
+        return Envelope {
+            attack: self.attack * factor.max(0.0),
+            ..*self
+        };
+    }
+
+    /// Returns the time in seconds at which a stage starts, counted from the key press.
+    fn stage_start(&self, stage: EnvelopeStage) -> f32 {
+        return match stage {
+            EnvelopeStage::Delay => 0.0,
+            EnvelopeStage::Attack => self.delay,
+            EnvelopeStage::Hold => self.delay + self.attack,
+            EnvelopeStage::Decay => self.delay + self.attack + self.hold,
+        };
+    }
+
+    fn stage_end(&self, stage: EnvelopeStage) -> f32 {
+        return match stage {
+            EnvelopeStage::Delay => self.delay,
+            EnvelopeStage::Attack => self.delay + self.attack,
+            EnvelopeStage::Hold => self.delay + self.attack + self.hold,
+            EnvelopeStage::Decay => self.delay + self.attack + self.hold + self.decay,
+        };
+    }
+
+    /// Returns the level at the end of a stage.
+    fn stage_end_level(&self, stage: EnvelopeStage, start_level: f32) -> f32 {
+        return match stage {
+            EnvelopeStage::Delay => start_level,
+            EnvelopeStage::Attack | EnvelopeStage::Hold => 1.0,
+            EnvelopeStage::Decay => self.sustain,
+        };
+    }
+
+    /// Returns the time within the envelope and the level the attack starts from, after wrapping
+    /// the time around the loop.
+    fn looped_time(&self, time: f32, start_level: f32) -> (f32, f32) {
+        let loop_start = match self.loop_start {
+            Some(loop_start) if loop_start <= self.loop_end => loop_start,
+            _ => return (time, start_level),
+        };
+
+        let start = self.stage_start(loop_start);
+        let end = self.stage_end(self.loop_end);
+        if time < end || end <= start {
+            return (time, start_level);
+        }
+
+        // after the first pass, the attack starts from where the loop ended
+        let time = start + (time - start) % (end - start);
+        return (time, self.stage_end_level(self.loop_end, start_level));
+    }
+
+    /// Returns the volume of a voice and whether it is still sounding. The volume is scaled by
+    /// `velocity`, the velocity response of the voice from 0 to 1.
+    pub fn evaluate(&self, message: &KeyState, velocity: f32) -> (f32, bool) {
+        let velocity = velocity.max(1e-6); // the start volume is divided by it
+        let start_volume = message.start_volume / velocity;
+        let (time, start_level) = self.looped_time(message.time_since_pressed, start_volume);
+        let release_time = message.time_since_released;
+
+        let mut value;
+        if time < self.delay {
+            value = start_level;
+        } else if time < self.delay + self.attack {
+            let x = self.attack_curve.apply((time - self.delay) / self.attack);
+            value = start_level + (1.0 - start_level) * x;
+        } else if time < self.delay + self.attack + self.hold {
+            value = 1.0;
+        } else if time < self.delay + self.attack + self.hold + self.decay {
+            let x = self.decay_curve.apply((time - self.delay - self.attack - self.hold) / self.decay);
+            value = 1.0 - x * (1.0 - self.sustain);
+        } else {
+            value = self.sustain;
+        }
+
+        if message.is_released {
+            if release_time >= self.release {
+                return (0.0, false); // release is over
+            }
+            value *= (1.0 - self.release_curve.apply(release_time / self.release)) * start_volume;
+        }
+
+        if message.is_stolen {
+            if message.time_since_stolen >= STEAL_FADE_TIME {
+                return (0.0, false); // voice has been taken over by another note
+            }
+            value *= 1.0 - message.time_since_stolen / STEAL_FADE_TIME;
+        }
+
+        return (value * velocity, true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pressed(time: f32) -> KeyState {
+        return KeyState {
+            is_active: true,
+            time_since_pressed: time,
+            ..KeyState::default()
+        };
+    }
+
+    fn released(time: f32, start_volume: f32) -> KeyState {
+        return KeyState {
+            is_active: true,
+            is_released: true,
+            time_since_pressed: 1.0,
+            time_since_released: time,
+            start_volume,
+            ..KeyState::default()
+        };
+    }
+
+    fn level(envelope: &Envelope, voice: &KeyState) -> f32 {
+        let (level, is_active) = envelope.evaluate(voice, 1.0);
+        assert!(is_active);
+        return level;
+    }
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-5, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn evaluates_every_stage() {
+        let mut envelope = Envelope::new(0.2, 0.2, 0.5, 0.3);
+        envelope.delay = 0.1;
+        envelope.hold = 0.1;
+
+        assert_near(level(&envelope, &pressed(0.05)), 0.0);
+        assert_near(level(&envelope, &pressed(0.2)), 0.5);
+        assert_near(level(&envelope, &pressed(0.35)), 1.0);
+        assert_near(level(&envelope, &pressed(0.5)), 0.75);
+        assert_near(level(&envelope, &pressed(2.0)), 0.5);
+    }
+
+    #[test]
+    fn release_fades_out_and_ends() {
+        let envelope = Envelope::new(0.0, 0.0, 1.0, 0.4);
+
+        let start = level(&envelope, &released(0.0, 1.0));
+        assert_near(start, 1.0);
+        assert_near(level(&envelope, &released(0.2, 1.0)), start * 0.5);
+        assert_eq!(envelope.evaluate(&released(0.4, 1.0), 1.0), (0.0, false));
+    }
+
+    #[test]
+    fn loops_between_stages() {
+        let mut envelope = Envelope::new(0.1, 0.1, 0.5, 0.3);
+        envelope.loop_start = Some(EnvelopeStage::Attack);
+        envelope.loop_end = EnvelopeStage::Decay;
+
+        // the first pass attacks from 0, later passes from the level the loop ended at
+        assert_near(level(&envelope, &pressed(0.05)), 0.5);
+        assert_near(level(&envelope, &pressed(0.25)), 0.75);
+        assert_near(level(&envelope, &pressed(0.45)), 0.75);
+        assert_near(level(&envelope, &pressed(0.35)), 0.75);
+    }
+
+    #[test]
+    fn loop_is_ignored_if_it_ends_before_it_starts() {
+        let mut envelope = Envelope::new(0.1, 0.1, 0.5, 0.3);
+        envelope.loop_start = Some(EnvelopeStage::Decay);
+        envelope.loop_end = EnvelopeStage::Attack;
+
+        assert_near(level(&envelope, &pressed(2.0)), 0.5);
+    }
+
+    #[test]
+    fn zero_length_attack_starts_at_full_level() {
+        let envelope = Envelope::new(0.0, 0.2, 0.5, 0.3);
+
+        assert_near(level(&envelope, &pressed(0.0)), 1.0);
+        assert_near(level(&envelope, &pressed(0.1)), 0.75);
+    }
+
+    #[test]
+    fn zero_length_attack_and_decay_go_to_sustain() {
+        let envelope = Envelope::new(0.0, 0.0, 0.5, 0.3);
+
+        assert_near(level(&envelope, &pressed(0.0)), 0.5);
+    }
+
+    #[test]
+    fn zero_length_release_ends_right_away() {
+        let envelope = Envelope::new(0.1, 0.1, 0.5, 0.0);
+
+        assert_eq!(envelope.evaluate(&released(0.0, 0.5), 1.0), (0.0, false));
+    }
+}
@@ -0,0 +1,150 @@
+/// An event that changes the state of the synth.
+#[derive(Clone, Copy)]
+pub enum SynthEvent {
+    NoteOn { channel: u8, key: u8, velocity: u8 },
+    NoteOff { channel: u8, key: u8 },
+    /// value goes from -8192 to 8191, 0 is the center
+    PitchBend { channel: u8, value: i16 },
+    ControlChange { channel: u8, controller: u8, value: u8 },
+}
+
+/// An event together with the sample offset within the next rendered block at which it applies.
+#[derive(Clone, Copy)]
+pub struct TimedEvent {
+    pub offset: usize,
+    pub event: SynthEvent,
+}
+
+/// An event together with the frame of the audio context at which it applies.
+#[derive(Clone, Copy)]
+pub struct ScheduledEvent {
+    pub frame: u64,
+    pub event: SynthEvent,
+}
+
+/// A queue of events waiting to be rendered. Its capacity is reserved up front, so pushing never
+/// allocates on the audio thread.
+pub struct EventQueue {
+    events: Vec<ScheduledEvent>,
+}
+
+impl EventQueue {
+    pub fn new(capacity: usize) -> EventQueue {
+        return EventQueue {
+            events: Vec::with_capacity(capacity),
+        };
+    }
+
+    /// Adds an event to the queue, keeping the queue sorted by frame. Events with the same frame
+    /// keep the order they were pushed in. Returns false if the queue is full and the event was
+    /// not added.
+    pub fn push(&mut self, event: ScheduledEvent) -> bool {
+        if self.events.len() == self.events.capacity() {
+            return false;
+        }
+
+        let index = self.events.iter()
+            .position(|queued| queued.frame > event.frame)
+            .unwrap_or(self.events.len());
+        self.events.insert(index, event);
+        return true;
+    }
+
+    /// Removes and returns the earliest event if it is due at or before `frame`.
+    pub fn pop_due(&mut self, frame: u64) -> Option<ScheduledEvent> {
+        if self.events.first()?.frame > frame {
+            return None;
+        }
+
+        return Some(self.events.remove(0));
+    }
+
+    /// Moves all events due before the end of the block starting at frame `start` into `out`, with
+    /// their offsets within the block. Events that are already late get offset 0.
+    pub fn take_block(&mut self, start: u64, len: usize, out: &mut Vec<TimedEvent>) {
+        let end = start + len as u64;
+        let count = self.events.iter()
+            .position(|queued| queued.frame >= end)
+            .unwrap_or(self.events.len());
+
+        for queued in self.events.drain(..count) {
+            out.push(TimedEvent {
+                offset: queued.frame.saturating_sub(start) as usize,
+                event: queued.event,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Schedules a note on, using the key to tell the events apart.
+    fn note_on(frame: u64, key: u8) -> ScheduledEvent {
+        return ScheduledEvent {
+            frame,
+            event: SynthEvent::NoteOn { channel: 0, key, velocity: 100 },
+        };
+    }
+
+    fn key(event: &SynthEvent) -> u8 {
+        return match event {
+            SynthEvent::NoteOn { key, .. } => *key,
+            _ => panic!("expected a note on"),
+        };
+    }
+
+    #[test]
+    fn events_are_sorted_by_frame_and_keep_their_order_within_a_frame() {
+        let mut queue = EventQueue::new(8);
+        for event in [note_on(20, 1), note_on(10, 2), note_on(20, 3), note_on(10, 4), note_on(0, 5)] {
+            assert!(queue.push(event));
+        }
+
+        let mut keys = Vec::new();
+        while let Some(event) = queue.pop_due(u64::MAX) {
+            keys.push(key(&event.event));
+        }
+
+        assert_eq!(keys, vec![5, 2, 4, 1, 3]);
+    }
+
+    #[test]
+    fn push_fails_when_the_queue_is_full() {
+        let mut queue = EventQueue::new(2);
+        assert!(queue.push(note_on(0, 1)));
+        assert!(queue.push(note_on(0, 2)));
+        assert!(!queue.push(note_on(0, 3)));
+    }
+
+    #[test]
+    fn pop_due_only_returns_due_events() {
+        let mut queue = EventQueue::new(4);
+        queue.push(note_on(100, 1));
+
+        assert!(queue.pop_due(99).is_none());
+        assert_eq!(queue.pop_due(100).map(|event| key(&event.event)), Some(1));
+        assert!(queue.pop_due(u64::MAX).is_none());
+    }
+
+    #[test]
+    fn take_block_returns_the_offsets_within_the_block() {
+        let mut queue = EventQueue::new(8);
+        for event in [note_on(90, 1), note_on(100, 2), note_on(150, 3), note_on(227, 4), note_on(228, 5)] {
+            queue.push(event);
+        }
+
+        let mut out = Vec::new();
+        queue.take_block(100, 128, &mut out);
+
+        let taken = out.iter().map(|event| (event.offset, key(&event.event))).collect::<Vec<(usize, u8)>>();
+        assert_eq!(taken, vec![(0, 1), (0, 2), (50, 3), (127, 4)]);
+
+        // the event after the block stays queued for the next one
+        out.clear();
+        queue.take_block(228, 128, &mut out);
+        let taken = out.iter().map(|event| (event.offset, key(&event.event))).collect::<Vec<(usize, u8)>>();
+        assert_eq!(taken, vec![(0, 5)]);
+    }
+}
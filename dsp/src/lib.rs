@@ -0,0 +1,20 @@
+//! The platform independent core of the synth: waves, Sytrus shaping, envelopes, the wave table
+//! synth and the MIDI bridge driving it. It has no wasm dependencies, so the app and the audio
+//! worker share the exact same code, and it can be built and run natively.
+
+pub mod bridge;
+pub mod cc_map;
+pub mod channel;
+pub mod envelope;
+pub mod events;
+pub mod midi_parser;
+pub mod params;
+pub mod rand;
+pub mod scala;
+pub mod synth;
+pub mod sytrus;
+pub mod tuning;
+pub mod unison;
+pub mod velocity;
+pub mod waves;
+pub mod wavetable;
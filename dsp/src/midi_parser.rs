@@ -0,0 +1,250 @@
+/// A parsed MIDI 1.0 message. Channels go from 0 to 15.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MidiEvent {
+    NoteOff { channel: u8, note: u8, velocity: u8 },
+    /// always has a velocity above 0, a note on with velocity 0 is parsed as a [MidiEvent::NoteOff]
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    PolyPressure { channel: u8, note: u8, pressure: u8 },
+    ControlChange { channel: u8, controller: u8, value: u8 },
+    ProgramChange { channel: u8, program: u8 },
+    ChannelPressure { channel: u8, pressure: u8 },
+    /// value goes from -8192 to 8191, 0 is the center position
+    PitchBend { channel: u8, value: i16 },
+    /// the bytes between the 0xF0 and 0xF7 status bytes
+    SysEx(Vec<u8>),
+    TimeCodeQuarterFrame(u8),
+    SongPosition(u16),
+    SongSelect(u8),
+    TuneRequest,
+    /// a single byte real time message, like timing clock (0xF8), start (0xFA) or stop (0xFC)
+    RealTime(u8),
+}
+
+/// Turns a stream of MIDI bytes into [MidiEvent]s. Handles running status, real time messages
+/// in between other messages and SysEx messages of any length.
+pub struct MidiParser {
+    /// the status of the message being read, kept after a channel message for running status
+    status: Option<u8>,
+    data: [u8; 2],
+    data_len: usize,
+    /// the data of the SysEx message being read
+    sysex: Option<Vec<u8>>,
+}
+
+impl Default for MidiParser {
+    fn default() -> MidiParser {
+        return MidiParser::new();
+    }
+}
+
+impl MidiParser {
+    pub fn new() -> MidiParser {
+        return MidiParser {
+            status: None,
+            data: [0; 2],
+            data_len: 0,
+            sysex: None,
+        };
+    }
+
+    /// Parses the given bytes, calling `on_event` for every complete message.
+    pub fn parse<F>(&mut self, bytes: &[u8], mut on_event: F)
+        where F: FnMut(MidiEvent)
+    {
+        for byte in bytes {
+            self.feed(*byte, &mut on_event);
+        }
+    }
+
+    /// Parses a single byte, calling `on_event` for every message it completes.
+    pub fn feed<F>(&mut self, byte: u8, on_event: &mut F)
+        where F: FnMut(MidiEvent)
+    {
+        // real time messages may appear anywhere and do not affect running status
+        if byte >= 0xF8 {
+            on_event(MidiEvent::RealTime(byte));
+            return;
+        }
+
+        if byte & 0x80 == 0 {
+            self.feed_data(byte, on_event);
+            return;
+        }
+
+        // any status byte ends a SysEx message, though it should end with 0xF7
+        if let Some(sysex) = self.sysex.take() {
+            on_event(MidiEvent::SysEx(sysex));
+        }
+
+        self.data_len = 0;
+
+        match byte {
+            0xF0 => {
+                self.status = None;
+                self.sysex = Some(Vec::new());
+            }
+            0xF6 => {
+                self.status = None;
+                on_event(MidiEvent::TuneRequest);
+            }
+            // end of SysEx, or undefined system common messages
+            0xF4 | 0xF5 | 0xF7 => self.status = None,
+            _ => self.status = Some(byte),
+        }
+    }
+
+    fn feed_data<F>(&mut self, byte: u8, on_event: &mut F)
+        where F: FnMut(MidiEvent)
+    {
+        if let Some(sysex) = &mut self.sysex {
+            sysex.push(byte);
+            return;
+        }
+
+        let status = match self.status {
+            Some(status) => status,
+            None => return, // data without a status byte, nothing to attach it to
+        };
+
+        self.data[self.data_len] = byte;
+        self.data_len += 1;
+
+        if self.data_len < data_length(status) {
+            return;
+        }
+
+        self.data_len = 0;
+
+        // system common messages cancel running status
+        if status >= 0xF0 {
+            self.status = None;
+        }
+
+        on_event(decode(status, self.data));
+    }
+}
+
+/// Returns the number of data bytes following the given status byte.
+fn data_length(status: u8) -> usize {
+    return match status & 0xF0 {
+        0xC0 | 0xD0 => 1,
+        0xF0 => match status {
+            0xF1 | 0xF3 => 1,
+            0xF2 => 2,
+            _ => 0,
+        },
+        _ => 2,
+    };
+}
+
+fn decode(status: u8, data: [u8; 2]) -> MidiEvent {
+    let channel = status & 0x0F;
+
+    return match status & 0xF0 {
+        0x80 => MidiEvent::NoteOff { channel, note: data[0], velocity: data[1] },
+        0x90 if data[1] == 0 => MidiEvent::NoteOff { channel, note: data[0], velocity: 0 },
+        0x90 => MidiEvent::NoteOn { channel, note: data[0], velocity: data[1] },
+        0xA0 => MidiEvent::PolyPressure { channel, note: data[0], pressure: data[1] },
+        0xB0 => MidiEvent::ControlChange { channel, controller: data[0], value: data[1] },
+        0xC0 => MidiEvent::ProgramChange { channel, program: data[0] },
+        0xD0 => MidiEvent::ChannelPressure { channel, pressure: data[0] },
+        0xE0 => MidiEvent::PitchBend {
+            channel,
+            value: ((data[1] as i16) << 7 | data[0] as i16) - 8192,
+        },
+        _ => match status {
+            0xF1 => MidiEvent::TimeCodeQuarterFrame(data[0]),
+            0xF2 => MidiEvent::SongPosition((data[1] as u16) << 7 | data[0] as u16),
+            _ => MidiEvent::SongSelect(data[0]),
+        },
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Vec<MidiEvent> {
+        let mut events = Vec::new();
+        MidiParser::new().parse(bytes, |event| events.push(event));
+        return events;
+    }
+
+    #[test]
+    fn running_status_repeats_the_last_channel_message() {
+        assert_eq!(parse(&[0x91, 60, 100, 62, 90, 0xB0, 1, 64, 2]), vec![
+            MidiEvent::NoteOn { channel: 1, note: 60, velocity: 100 },
+            MidiEvent::NoteOn { channel: 1, note: 62, velocity: 90 },
+            MidiEvent::ControlChange { channel: 0, controller: 1, value: 64 },
+        ]);
+    }
+
+    #[test]
+    fn note_on_with_velocity_0_is_a_note_off() {
+        assert_eq!(parse(&[0x90, 60, 100, 60, 0]), vec![
+            MidiEvent::NoteOn { channel: 0, note: 60, velocity: 100 },
+            MidiEvent::NoteOff { channel: 0, note: 60, velocity: 0 },
+        ]);
+    }
+
+    #[test]
+    fn pitch_bend_is_decoded_from_14_bits() {
+        assert_eq!(parse(&[0xE0, 0x00, 0x40, 0x00, 0x00, 0x7F, 0x7F, 0x01, 0x00, 0x7F, 0x3F]), vec![
+            MidiEvent::PitchBend { channel: 0, value: 0 },
+            MidiEvent::PitchBend { channel: 0, value: -8192 },
+            MidiEvent::PitchBend { channel: 0, value: 8191 },
+            MidiEvent::PitchBend { channel: 0, value: -8191 },
+            MidiEvent::PitchBend { channel: 0, value: -1 },
+        ]);
+    }
+
+    #[test]
+    fn real_time_messages_do_not_interrupt_other_messages() {
+        assert_eq!(parse(&[0xF0, 0x7E, 0xF8, 0x01, 0xFA, 0xF7, 0x90, 60, 0xF8, 100]), vec![
+            MidiEvent::RealTime(0xF8),
+            MidiEvent::RealTime(0xFA),
+            MidiEvent::SysEx(vec![0x7E, 0x01]),
+            MidiEvent::RealTime(0xF8),
+            MidiEvent::NoteOn { channel: 0, note: 60, velocity: 100 },
+        ]);
+    }
+
+    #[test]
+    fn any_status_byte_ends_a_sysex_message() {
+        assert_eq!(parse(&[0xF0, 1, 2, 0x80, 60, 0]), vec![
+            MidiEvent::SysEx(vec![1, 2]),
+            MidiEvent::NoteOff { channel: 0, note: 60, velocity: 0 },
+        ]);
+    }
+
+    #[test]
+    fn truncated_messages_and_stray_data_are_dropped() {
+        // data before any status byte
+        assert_eq!(parse(&[60, 100, 0xC2, 5]), vec![MidiEvent::ProgramChange { channel: 2, program: 5 }]);
+
+        // a note on cut short by another message
+        assert_eq!(parse(&[0x90, 60, 0xB0, 7, 100]), vec![
+            MidiEvent::ControlChange { channel: 0, controller: 7, value: 100 },
+        ]);
+
+        // system common messages cancel running status
+        assert_eq!(parse(&[0x90, 60, 100, 0xF2, 0, 1, 62, 100, 0xF6, 64]), vec![
+            MidiEvent::NoteOn { channel: 0, note: 60, velocity: 100 },
+            MidiEvent::SongPosition(128),
+            MidiEvent::TuneRequest,
+        ]);
+
+        // an end of SysEx without a start
+        assert_eq!(parse(&[0xF7, 1, 2]), vec![]);
+    }
+
+    #[test]
+    fn messages_can_be_split_across_calls() {
+        let mut parser = MidiParser::new();
+        let mut events = Vec::new();
+        parser.parse(&[0x90, 60], |event| events.push(event));
+        parser.parse(&[100], |event| events.push(event));
+
+        assert_eq!(events, vec![MidiEvent::NoteOn { channel: 0, note: 60, velocity: 100 }]);
+    }
+}
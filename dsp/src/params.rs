@@ -0,0 +1,211 @@
+/// A parameter of the synth that can be changed while it is playing. The app sends the number of
+/// the parameter to the audio worker.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SynthParam {
+    /// the output volume, from 0 to 1
+    Volume = 0,
+    /// the wave table interpolation mode, see [crate::wavetable::Interpolation]
+    Interpolation = 1,
+    /// the time in seconds over which a new wave table is faded in
+    WaveTableCrossfade = 2,
+    /// how many semitones the pitch bend wheel bends at its extremes, on all channels
+    PitchBendRange = 3,
+    /// a second volume factor, from 0 to 1, usually controlled by the expression pedal
+    Expression = 4,
+    /// the envelope attack time in seconds
+    Attack = 5,
+    /// the envelope decay time in seconds
+    Decay = 6,
+    /// the envelope sustain level, from 0 to 1
+    Sustain = 7,
+    /// the envelope release time in seconds
+    Release = 8,
+    /// the Sytrus shape parameters, from -1 to 1, see [crate::sytrus::SytrusParams]
+    SytrusShape = 9,
+    SytrusTension = 10,
+    SytrusSkew = 11,
+    SytrusSineShaper = 12,
+    SytrusPreFilter = 13,
+    /// the vibrato depth in semitones
+    VibratoDepth = 14,
+    /// the vibrato rate in hz
+    VibratoRate = 15,
+    /// poly, mono or legato, see [crate::bridge::VoiceMode]
+    VoiceMode = 16,
+    /// which held key sounds in mono and legato mode, see [crate::bridge::NotePriority]
+    NotePriority = 17,
+    /// the glide time in seconds, 0 turns glide off
+    GlideTime = 18,
+    /// whether the glide time is per glide or per octave, see [crate::bridge::GlideMode]
+    GlideMode = 19,
+    /// the envelope delay time in seconds
+    Delay = 20,
+    /// the envelope hold time in seconds
+    Hold = 21,
+    /// the curves of the envelope segments, see [crate::cc_map::Curve]
+    AttackCurve = 22,
+    DecayCurve = 23,
+    ReleaseCurve = 24,
+    /// the first looped envelope stage, see [crate::envelope::EnvelopeStage], negative to not loop
+    EnvelopeLoopStart = 25,
+    /// the last looped envelope stage
+    EnvelopeLoopEnd = 26,
+    /// how velocity maps to loudness, see [crate::velocity::VelocityCurve]
+    VelocityCurve = 27,
+    /// how much the velocity changes the loudness, from 0 to 1
+    VelocitySensitivity = 28,
+    /// how much softer notes are darkened, from 0 to 1
+    VelocityToBrightness = 29,
+    /// how much harder notes shorten the attack, from 0 to 1
+    VelocityToAttack = 30,
+    /// the pan of voices on middle C, from -1 for left to 1 for right
+    Pan = 31,
+    /// how far voices are panned by their key, from -1 to 1
+    PanSpread = 32,
+    /// the balance of the output, from -1 for left to 1 for right
+    Balance = 33,
+    /// the number of oscillators stacked on every note, from 1 to 16
+    UnisonVoices = 34,
+    /// the distance in semitones between the lowest and the highest unison oscillator
+    UnisonDetune = 35,
+    /// how the unison oscillators are spread between the outermost ones, see [crate::cc_map::Curve]
+    UnisonDetuneCurve = 36,
+    /// how far the outermost unison oscillators are panned, from 0 to 1
+    UnisonWidth = 37,
+    /// how random the start phases of the unison oscillators are, from 0 to 1
+    UnisonRandomPhase = 38,
+    /// the position within a multi frame wave table, from 0 for the first to 1 for the last frame
+    WavePosition = 39,
+    /// how far the volume envelope moves the wave table position, from -1 to 1
+    WavePositionEnvelope = 40,
+    /// how far the lfo moves the wave table position, from 0 to 1
+    WavePositionLfoDepth = 41,
+    /// the rate of the wave table position lfo in hz
+    WavePositionLfoRate = 42,
+    /// how far the velocity moves the wave table position, from -1 to 1
+    WavePositionVelocity = 43,
+    /// the Sytrus shape of the last frame, the frames in between sweep from the shape to it
+    SytrusShapeEnd = 44,
+    /// the number of frames the Sytrus parameters generate, from 1 to 64
+    SytrusFrames = 45,
+    /// the Sytrus tension, skew, sine shaper and pre filter of the last frame, like
+    /// [SynthParam::SytrusShapeEnd]
+    SytrusTensionEnd = 46,
+    SytrusSkewEnd = 47,
+    SytrusSineShaperEnd = 48,
+    SytrusPreFilterEnd = 49,
+}
+
+impl SynthParam {
+    pub const ALL: [SynthParam; 50] = [
+        SynthParam::Volume,
+        SynthParam::Interpolation,
+        SynthParam::WaveTableCrossfade,
+        SynthParam::PitchBendRange,
+        SynthParam::Expression,
+        SynthParam::Attack,
+        SynthParam::Decay,
+        SynthParam::Sustain,
+        SynthParam::Release,
+        SynthParam::SytrusShape,
+        SynthParam::SytrusTension,
+        SynthParam::SytrusSkew,
+        SynthParam::SytrusSineShaper,
+        SynthParam::SytrusPreFilter,
+        SynthParam::VibratoDepth,
+        SynthParam::VibratoRate,
+        SynthParam::VoiceMode,
+        SynthParam::NotePriority,
+        SynthParam::GlideTime,
+        SynthParam::GlideMode,
+        SynthParam::Delay,
+        SynthParam::Hold,
+        SynthParam::AttackCurve,
+        SynthParam::DecayCurve,
+        SynthParam::ReleaseCurve,
+        SynthParam::EnvelopeLoopStart,
+        SynthParam::EnvelopeLoopEnd,
+        SynthParam::VelocityCurve,
+        SynthParam::VelocitySensitivity,
+        SynthParam::VelocityToBrightness,
+        SynthParam::VelocityToAttack,
+        SynthParam::Pan,
+        SynthParam::PanSpread,
+        SynthParam::Balance,
+        SynthParam::UnisonVoices,
+        SynthParam::UnisonDetune,
+        SynthParam::UnisonDetuneCurve,
+        SynthParam::UnisonWidth,
+        SynthParam::UnisonRandomPhase,
+        SynthParam::WavePosition,
+        SynthParam::WavePositionEnvelope,
+        SynthParam::WavePositionLfoDepth,
+        SynthParam::WavePositionLfoRate,
+        SynthParam::WavePositionVelocity,
+        SynthParam::SytrusShapeEnd,
+        SynthParam::SytrusFrames,
+        SynthParam::SytrusTensionEnd,
+        SynthParam::SytrusSkewEnd,
+        SynthParam::SytrusSineShaperEnd,
+        SynthParam::SytrusPreFilterEnd,
+    ];
+
+    pub fn from_u8(value: u8) -> Option<SynthParam> {
+        return SynthParam::ALL.get(value as usize).copied();
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            SynthParam::Volume => "Volume",
+            SynthParam::Interpolation => "Interpolation",
+            SynthParam::WaveTableCrossfade => "Wave Table Crossfade",
+            SynthParam::PitchBendRange => "Pitch Bend Range",
+            SynthParam::Expression => "Expression",
+            SynthParam::Attack => "Attack",
+            SynthParam::Decay => "Decay",
+            SynthParam::Sustain => "Sustain",
+            SynthParam::Release => "Release",
+            SynthParam::SytrusShape => "Sytrus Shape",
+            SynthParam::SytrusTension => "Sytrus Tension",
+            SynthParam::SytrusSkew => "Sytrus Skew",
+            SynthParam::SytrusSineShaper => "Sytrus Sine Shaper",
+            SynthParam::SytrusPreFilter => "Sytrus Pre Filter",
+            SynthParam::VibratoDepth => "Vibrato Depth",
+            SynthParam::VibratoRate => "Vibrato Rate",
+            SynthParam::VoiceMode => "Voice Mode",
+            SynthParam::NotePriority => "Note Priority",
+            SynthParam::GlideTime => "Glide Time",
+            SynthParam::GlideMode => "Glide Mode",
+            SynthParam::Delay => "Delay",
+            SynthParam::Hold => "Hold",
+            SynthParam::AttackCurve => "Attack Curve",
+            SynthParam::DecayCurve => "Decay Curve",
+            SynthParam::ReleaseCurve => "Release Curve",
+            SynthParam::EnvelopeLoopStart => "Envelope Loop Start",
+            SynthParam::EnvelopeLoopEnd => "Envelope Loop End",
+            SynthParam::VelocityCurve => "Velocity Curve",
+            SynthParam::VelocitySensitivity => "Velocity Sensitivity",
+            SynthParam::VelocityToBrightness => "Velocity to Brightness",
+            SynthParam::VelocityToAttack => "Velocity to Attack",
+            SynthParam::Pan => "Pan",
+            SynthParam::PanSpread => "Key Spread",
+            SynthParam::Balance => "Balance",
+            SynthParam::UnisonVoices => "Unison Voices",
+            SynthParam::UnisonDetune => "Unison Detune",
+            SynthParam::UnisonDetuneCurve => "Unison Detune Curve",
+            SynthParam::UnisonWidth => "Unison Width",
+            SynthParam::UnisonRandomPhase => "Unison Random Phase",
+            SynthParam::WavePosition => "Wave Position",
+            SynthParam::WavePositionEnvelope => "Wave Position Envelope",
+            SynthParam::WavePositionLfoDepth => "Wave Position LFO Depth",
+            SynthParam::WavePositionLfoRate => "Wave Position LFO Rate",
+            SynthParam::WavePositionVelocity => "Wave Position Velocity",
+            SynthParam::SytrusShapeEnd => "Sytrus Shape End",
+            SynthParam::SytrusFrames => "Sytrus Frames",
+            SynthParam::SytrusTensionEnd => "Sytrus Tension End",
+            SynthParam::SytrusSkewEnd => "Sytrus Skew End",
+            SynthParam::SytrusSineShaperEnd => "Sytrus Sine Shaper End",
+            SynthParam::SytrusPreFilterEnd => "Sytrus Pre Filter End",
+        };
+    }
+}
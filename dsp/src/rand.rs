@@ -8,7 +8,8 @@ impl SimpleRng {
         SimpleRng { seed }
     }
 
-    pub fn next(&mut self) -> f32 {
+    /// Returns a random number from 0 to 1.
+    pub fn next_f32(&mut self) -> f32 {
         self.seed = xorshift(self.seed);
         (self.seed as f32) / (u32::MAX as f32)
     }
@@ -0,0 +1,317 @@
+use crate::tuning::KEY_COUNT;
+
+/// A scale read from a Scala .scl file.
+#[derive(Clone, PartialEq)]
+pub struct Scale {
+    pub description: String,
+    /// the frequency ratio of every degree to the first one. The first degree itself is not
+    /// included, the last one is the period the scale repeats at, usually the octave.
+    pub ratios: Vec<f64>,
+}
+
+impl Scale {
+    /// The 12-TET scale, used when no scale file is loaded.
+    pub fn equal_temperament() -> Scale {
+        return Scale {
+            description: "12-TET".to_string(),
+            ratios: (1..=12).map(|degree| 2.0f64.powf(degree as f64 / 12.0)).collect(),
+        };
+    }
+
+    /// Returns the ratio of a degree, which may lie beyond the period or below the first degree.
+    fn ratio(&self, degree: i32) -> f64 {
+        let len = self.ratios.len() as i32;
+        let period = self.ratios[self.ratios.len() - 1];
+
+        let periods = degree.div_euclid(len);
+        let degree = degree.rem_euclid(len);
+
+        let ratio = if degree == 0 { 1.0 } else { self.ratios[degree as usize - 1] };
+        return ratio * period.powi(periods);
+    }
+}
+
+/// Maps MIDI keys to scale degrees, read from a Scala .kbm file.
+#[derive(Clone, PartialEq)]
+pub struct KeyboardMapping {
+    pub first_key: u8,
+    pub last_key: u8,
+    /// the key the first entry of the mapping is played on
+    pub middle_key: u8,
+    /// the key that is tuned to the reference frequency
+    pub reference_key: u8,
+    pub reference_frequency: f64,
+    /// the degree the mapping repeats at, in degrees of the scale
+    pub octave_degree: i32,
+    /// the degree played by each key, starting at the middle key, None if the key is unmapped.
+    /// An empty mapping plays every degree in order.
+    pub degrees: Vec<Option<i32>>,
+}
+
+impl KeyboardMapping {
+    /// The standard mapping: every degree in order, starting at middle C, with A4 at the given
+    /// frequency.
+    pub fn standard(reference_frequency: f64) -> KeyboardMapping {
+        return KeyboardMapping {
+            first_key: 0,
+            last_key: 127,
+            middle_key: 60,
+            reference_key: 69,
+            reference_frequency,
+            octave_degree: 0,
+            degrees: Vec::new(),
+        };
+    }
+
+    /// Returns the degree of the scale played by a key.
+    fn degree(&self, key: u8, scale: &Scale) -> Option<i32> {
+        if key < self.first_key || key > self.last_key {
+            return None;
+        }
+
+        let offset = key as i32 - self.middle_key as i32;
+
+        if self.degrees.is_empty() {
+            return Some(offset);
+        }
+
+        let len = self.degrees.len() as i32;
+        let octave_degree = if self.octave_degree > 0 { self.octave_degree } else { scale.ratios.len() as i32 };
+
+        let degree = self.degrees[offset.rem_euclid(len) as usize]?;
+        return Some(degree + offset.div_euclid(len) * octave_degree);
+    }
+}
+
+/// Returns the frequency of every MIDI key, 0 for keys that are not mapped.
+pub fn frequencies(scale: &Scale, mapping: &KeyboardMapping) -> Result<Vec<f32>, String> {
+    let reference_degree = mapping.degree(mapping.reference_key, scale)
+        .ok_or("The reference key is not mapped")?;
+    let reference_ratio = scale.ratio(reference_degree);
+
+    return Ok((0..KEY_COUNT).map(|key| {
+        match mapping.degree(key as u8, scale) {
+            Some(degree) => (mapping.reference_frequency * scale.ratio(degree) / reference_ratio) as f32,
+            None => 0.0,
+        }
+    }).collect());
+}
+
+/// Returns the lines of a Scala file that are not comments.
+fn lines(text: &str) -> impl Iterator<Item=&str> {
+    return text.lines().filter(|line| !line.starts_with('!'));
+}
+
+/// Parses the contents of a Scala .scl file.
+pub fn parse_scl(text: &str) -> Result<Scale, String> {
+    let mut lines = lines(text);
+
+    let description = lines.next().ok_or("The scale file is empty")?.trim().to_string();
+    let count = parse_number::<usize>(lines.next(), "note count")?;
+
+    let ratios = lines
+        .filter(|line| !line.trim().is_empty())
+        .take(count)
+        .map(parse_pitch)
+        .collect::<Result<Vec<f64>, String>>()?;
+
+    if ratios.len() < count || count == 0 {
+        return Err(format!("The scale should have {} notes, but has {}", count, ratios.len()));
+    }
+
+    return Ok(Scale {
+        description,
+        ratios,
+    });
+}
+
+/// Parses a pitch of a scale file: cents if it contains a period, otherwise a ratio like 3/2 or 2.
+fn parse_pitch(line: &str) -> Result<f64, String> {
+    // anything after the value is a comment
+    let value = line.split_whitespace().next().unwrap_or("");
+
+    let ratio = if value.contains('.') {
+        let cents = value.parse::<f64>().map_err(|_| format!("Invalid cents value '{}'", value))?;
+        (cents / 1200.0).exp2()
+    } else {
+        let mut parts = value.splitn(2, '/');
+        let numerator = parts.next().unwrap_or("").parse::<f64>();
+        let denominator = parts.next().unwrap_or("1").parse::<f64>();
+
+        match (numerator, denominator) {
+            (Ok(numerator), Ok(denominator)) if denominator > 0.0 => numerator / denominator,
+            _ => return Err(format!("Invalid ratio '{}'", value)),
+        }
+    };
+
+    if ratio <= 0.0 || !ratio.is_finite() {
+        return Err(format!("Invalid pitch '{}'", value));
+    }
+
+    return Ok(ratio);
+}
+
+/// Parses the contents of a Scala .kbm file.
+pub fn parse_kbm(text: &str) -> Result<KeyboardMapping, String> {
+    let mut lines = lines(text).filter(|line| !line.trim().is_empty());
+
+    let size = parse_number::<usize>(lines.next(), "map size")?;
+    let first_key = parse_number::<u8>(lines.next(), "first key")?;
+    let last_key = parse_number::<u8>(lines.next(), "last key")?;
+    let middle_key = parse_number::<u8>(lines.next(), "middle key")?;
+    let reference_key = parse_number::<u8>(lines.next(), "reference key")?;
+    let reference_frequency = parse_number::<f64>(lines.next(), "reference frequency")?;
+    let octave_degree = parse_number::<i32>(lines.next(), "octave degree")?;
+
+    let mut degrees = lines
+        .take(size)
+        .map(|line| {
+            let value = line.split_whitespace().next().unwrap_or("");
+            if value == "x" {
+                return Ok(None);
+            }
+            return value.parse::<i32>()
+                .map(Some)
+                .map_err(|_| format!("Invalid mapping entry '{}'", value));
+        })
+        .collect::<Result<Vec<Option<i32>>, String>>()?;
+
+    // missing entries at the end are unmapped
+    degrees.resize(size, None);
+
+    if reference_frequency <= 0.0 {
+        return Err("The reference frequency has to be above 0".to_string());
+    }
+
+    return Ok(KeyboardMapping {
+        first_key,
+        last_key,
+        middle_key,
+        reference_key,
+        reference_frequency,
+        octave_degree,
+        degrees,
+    });
+}
+
+fn parse_number<T: std::str::FromStr>(line: Option<&str>, name: &str) -> Result<T, String> {
+    let line = line.ok_or(format!("The {} is missing", name))?;
+    let value = line.split_whitespace().next().unwrap_or("");
+    return value.parse::<T>().map_err(|_| format!("Invalid {} '{}'", name, value));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuning::Tuning;
+
+    const PENTATONIC: &str = "! pentatonic.scl
+!
+A just pentatonic scale
+ 5
+!
+ 9/8
+ 5/4
+ 3/2
+ 5/3
+ 2/1
+";
+
+    /// Plays the pentatonic scale on the white keys C, D, E, G and A, leaving the others unmapped.
+    const WHITE_KEYS: &str = "! white_keys.kbm
+12
+0
+127
+60
+69
+440.0
+5
+0
+x
+1
+x
+2
+3
+x
+4
+x
+5
+x
+x
+";
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-3, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn parses_ratios_and_cents() {
+        let scale = parse_scl("cents\n2\n100.0\n2/1 the octave\n").unwrap();
+
+        assert_eq!(scale.description, "cents");
+        assert_eq!(scale.ratios.len(), 2);
+        assert!((scale.ratios[0] - 2.0f64.powf(1.0 / 12.0)).abs() < 1e-12);
+        assert_eq!(scale.ratios[1], 2.0);
+    }
+
+    #[test]
+    fn invalid_files_are_errors() {
+        assert!(parse_scl("").is_err());
+        assert!(parse_scl("too short\n3\n9/8\n2/1\n").is_err());
+        assert!(parse_scl("bad ratio\n1\n3/0\n").is_err());
+        assert!(parse_kbm("12\n0\n127\n60\n69\n0.0\n12\n").is_err());
+        assert!(parse_kbm("12\n0\n127\n60\n69\n440.0\n12\ny\n").is_err());
+    }
+
+    #[test]
+    fn equal_temperament_matches_the_tuning() {
+        let frequencies = frequencies(&Scale::equal_temperament(), &KeyboardMapping::standard(440.0)).unwrap();
+        let tuning = Tuning::from_frequencies(&frequencies);
+        let equal_temperament = Tuning::equal_temperament(440.0);
+
+        for key in 0..KEY_COUNT as u8 {
+            assert_near(tuning.pitch(key).unwrap(), equal_temperament.pitch(key).unwrap());
+        }
+    }
+
+    #[test]
+    fn standard_mapping_plays_every_degree_from_middle_c() {
+        let scale = parse_scl(PENTATONIC).unwrap();
+        let frequencies = frequencies(&scale, &KeyboardMapping::standard(440.0)).unwrap();
+
+        assert_near(frequencies[69], 440.0);
+        assert_near(frequencies[60], 132.0);
+        assert_near(frequencies[61], 148.5);
+        assert_near(frequencies[65], 264.0);
+        assert_near(frequencies[55], 66.0);
+        assert_near(frequencies[54], 55.0);
+    }
+
+    #[test]
+    fn keyboard_mapping_skips_unmapped_keys() {
+        let scale = parse_scl(PENTATONIC).unwrap();
+        let mapping = parse_kbm(WHITE_KEYS).unwrap();
+        let frequencies = frequencies(&scale, &mapping).unwrap();
+
+        assert_near(frequencies[69], 440.0);
+        assert_near(frequencies[60], 220.0);
+        assert_near(frequencies[67], 440.0 * 5.0 / 6.0);
+        assert_near(frequencies[72], 440.0);
+        assert_near(frequencies[48], 110.0);
+        assert_eq!(frequencies[61], 0.0);
+
+        let tuning = Tuning::from_frequencies(&frequencies);
+        assert_near(tuning.pitch(69).unwrap(), 69.0);
+        assert_near(tuning.pitch(60).unwrap(), 57.0);
+        assert!(tuning.pitch(61).is_none());
+    }
+
+    #[test]
+    fn unmapped_reference_key_is_an_error() {
+        let scale = parse_scl(PENTATONIC).unwrap();
+        let mut mapping = parse_kbm(WHITE_KEYS).unwrap();
+        mapping.reference_key = 61;
+
+        assert!(frequencies(&scale, &mapping).is_err());
+    }
+}
@@ -0,0 +1,552 @@
+use super::bridge::{KeyState, pan_gains, Synth};
+use super::envelope::Envelope;
+use super::params::SynthParam;
+use super::rand::SimpleRng;
+use super::sytrus::{MAX_SYTRUS_FRAMES, SytrusParams};
+use super::unison::{MAX_UNISON, Unison};
+use super::velocity::VelocityResponse;
+use super::wavetable::{Interpolation, MipMappedTable, read_table, WavePosition, WaveTable};
+
+/// The number of frames of a new wave table built per block. Building a frame runs a fourier
+/// transform and builds all of its band-limited levels, so a table of many frames is spread over
+/// several blocks.
+const FRAMES_PER_BLOCK: usize = 2;
+
+/// What the frames of a new wave table are built from.
+enum WaveTableSource {
+    Sytrus(SytrusParams),
+    /// samples sent from the app, split into frames of the given size as by [WaveTable::new]
+    Samples(Vec<f32>, usize),
+}
+
+pub struct WaveTableSynth {
+    /// the sample rate in hz
+    sample_rate: u32,
+
+    /// the frames of the wave table, with band-limited copies for higher notes
+    wave_table: WaveTable,
+
+    /// the wave table that is faded out after the wave table changed
+    previous_wave_table: WaveTable,
+
+    /// whether the previous wave table is still being faded out
+    crossfading: bool,
+
+    /// the wave table new frames are built into, a few frames per block. It takes the place of the
+    /// wave table once all of its frames are built.
+    next_wave_table: WaveTable,
+
+    /// the samples of the Sytrus frame being generated
+    sytrus_frame: Vec<f32>,
+
+    /// where voices read between the frames of the wave table
+    position: WavePosition,
+
+    /// the time in seconds over which a new wave table is faded in
+    crossfade_time: f32,
+
+    /// the number of samples rendered since the wave table changed
+    crossfade_elapsed: usize,
+
+    /// how the wave table is read between two of its samples
+    interpolation: Interpolation,
+
+    /// the volume envelope
+    envelope: Envelope,
+    velocity: VelocityResponse,
+
+    /// the parameters used to generate a new wave table when a Sytrus parameter is changed
+    sytrus: SytrusParams,
+
+    /// the wave table to build once the one being built is done. Only the latest change is kept,
+    /// so dragging a handle or sweeping a controller does not pile up work.
+    pending_wave_table: Option<WaveTableSource>,
+
+    /// what the next wave table is being built from, and how many of its frames are done
+    building_wave_table: Option<(WaveTableSource, usize)>,
+
+    /// the vibrato depth in semitones
+    vibrato_depth: f32,
+
+    /// the vibrato rate in hz
+    vibrato_rate: f32,
+
+    /// the detuned oscillators stacked on every note
+    unison: Unison,
+
+    /// picks the start phases of the unison oscillators
+    rng: SimpleRng,
+}
+
+impl WaveTableSynth {
+    pub fn new(sample_rate: u32, wave_table: Vec<f32>, envelope: Envelope) -> WaveTableSynth {
+        // the tables take each other's place when a new table is faded in, so all of them have
+        // room for the frames of a Sytrus table
+        let mut wave_table = WaveTable::new(&wave_table, 0);
+        wave_table.reserve_frames(MAX_SYTRUS_FRAMES);
+        let frame_size = wave_table.frame_size();
+
+        return WaveTableSynth {
+            sample_rate,
+            wave_table,
+            previous_wave_table: WaveTable::silent(frame_size, MAX_SYTRUS_FRAMES),
+            crossfading: false,
+            next_wave_table: WaveTable::silent(frame_size, MAX_SYTRUS_FRAMES),
+            sytrus_frame: vec![0.0; frame_size],
+            position: WavePosition::new(),
+            crossfade_time: 0.02,
+            crossfade_elapsed: 0,
+            interpolation: Interpolation::Linear,
+            envelope,
+            velocity: VelocityResponse::new(),
+            sytrus: SytrusParams::default(),
+            pending_wave_table: None,
+            building_wave_table: None,
+            vibrato_depth: 0.0,
+            vibrato_rate: 5.0,
+            unison: Unison::new(),
+            rng: SimpleRng::new(0x5eed_1234),
+        };
+    }
+
+    /// Interpolates between the closest samples in the wave table using the current interpolation
+    /// mode, crossfading between the two closest band-limited levels.
+    /// param phase: The position within one cycle of the wave, from 0 to 1.
+    /// param level: The band-limited level to read from, see [MipMappedTable::level_for_frequency].
+    fn interpolate(&self, table: &MipMappedTable, phase: f32, level: f32) -> f32 {
+        let level_floor = level.floor() as usize;
+        let level_fraction = level.fract();
+
+        let value = read_table(table.level(level_floor), phase, self.interpolation);
+        if level_fraction == 0.0 {
+            return value;
+        }
+
+        let next_value = read_table(table.level(level_floor + 1), phase, self.interpolation);
+        return value + (next_value - value) * level_fraction;
+    }
+
+    /// Reads a multi frame wave table, interpolating between the two frames around `position`.
+    fn interpolate_frames(&self, table: &WaveTable, phase: f32, level: f32, position: f32) -> f32 {
+        let (first, second, t) = table.frames_at(position);
+
+        let value = self.interpolate(first, phase, level);
+        if t == 0.0 {
+            return value;
+        }
+
+        let next_value = self.interpolate(second, phase, level);
+        return value + (next_value - value) * t;
+    }
+
+    /// Sets the Sytrus parameters. The wave table is built from them over the next blocks.
+    fn set_sytrus(&mut self, sytrus: SytrusParams) {
+        if sytrus == self.sytrus {
+            return;
+        }
+
+        self.sytrus = sytrus;
+        self.pending_wave_table = Some(WaveTableSource::Sytrus(sytrus));
+    }
+
+    /// Returns the size and the number of the frames of a wave table built from `source`. Sytrus
+    /// frames have the size of the frames of the current table.
+    fn frame_layout(&self, source: &WaveTableSource) -> (usize, usize) {
+        return match source {
+            WaveTableSource::Sytrus(sytrus) => (self.wave_table.frame_size(), sytrus.frame_count()),
+            WaveTableSource::Samples(samples, frame_size) => WaveTable::split_frames(samples.len(), *frame_size),
+        };
+    }
+
+    /// Makes room in the next wave table for the frames of `source`. This only allocates when the
+    /// frame size changes or there are more frames than reserved.
+    fn prepare_next_wave_table(&mut self, source: &WaveTableSource) {
+        let (frame_size, frame_count) = self.frame_layout(source);
+
+        if self.next_wave_table.frame_size() != frame_size {
+            self.next_wave_table = WaveTable::silent(frame_size, frame_count.max(MAX_SYTRUS_FRAMES));
+        }
+        self.next_wave_table.reserve_frames(frame_count);
+        self.sytrus_frame.resize(frame_size, 0.0);
+    }
+
+    /// Builds up to [FRAMES_PER_BLOCK] frames of the next wave table, and fades it in once all of
+    /// its frames are done. The tables and the frame buffer are reserved up front, so this does not
+    /// allocate unless the frame size changes.
+    fn build_wave_table(&mut self) {
+        let (source, mut done) = match self.building_wave_table.take() {
+            Some(building) => building,
+            None => match self.pending_wave_table.take() {
+                Some(source) => {
+                    self.prepare_next_wave_table(&source);
+                    (source, 0)
+                }
+                None => return,
+            },
+        };
+
+        let (frame_size, frame_count) = self.frame_layout(&source);
+        let end = (done + FRAMES_PER_BLOCK).min(frame_count);
+        while done < end {
+            match &source {
+                WaveTableSource::Sytrus(sytrus) => {
+                    sytrus.fill_frame(done, &mut self.sytrus_frame);
+                    self.next_wave_table.rebuild_frame(done, &self.sytrus_frame);
+                }
+                WaveTableSource::Samples(samples, _) => {
+                    self.next_wave_table.rebuild_frame(done, &samples[done * frame_size..(done + 1) * frame_size]);
+                }
+            }
+            done += 1;
+        }
+
+        if done < frame_count {
+            self.building_wave_table = Some((source, done));
+            return;
+        }
+
+        if self.next_wave_table.set_frame_count(frame_count) {
+            self.swap_in_next_wave_table();
+        }
+    }
+
+    /// Makes the next wave table the current one and fades out from what was audible before.
+    fn swap_in_next_wave_table(&mut self) {
+        self.fade_out_current_wave_table();
+        std::mem::swap(&mut self.wave_table, &mut self.next_wave_table);
+    }
+
+    /// Moves what is currently audible into the previous wave table, which is a mix of the
+    /// previous and the current table if the last fade has not finished, and restarts the fade.
+    /// Tables with frames of different sizes cannot be mixed, so then the fade starts from the
+    /// current table. The current table is left free to be replaced.
+    fn fade_out_current_wave_table(&mut self) {
+        let crossfade = self.crossfade_amount(0);
+        let mixed = crossfade < 1.0 && self.previous_wave_table.mix_in_place(&self.wave_table, crossfade);
+        if !mixed {
+            std::mem::swap(&mut self.previous_wave_table, &mut self.wave_table);
+        }
+
+        self.crossfading = true;
+        self.crossfade_elapsed = 0;
+    }
+
+    /// Returns how far the current wave table has been faded in, from 0 to 1, at the given
+    /// sample offset within the block being rendered.
+    fn crossfade_amount(&self, offset: usize) -> f32 {
+        let crossfade_samples = self.crossfade_time * self.sample_rate as f32;
+        if !self.crossfading || crossfade_samples <= 0.0 {
+            return 1.0;
+        }
+
+        return ((self.crossfade_elapsed + offset) as f32 / crossfade_samples).min(1.0);
+    }
+
+    pub fn set_envelope(&mut self, envelope: Envelope) {
+        self.envelope = envelope;
+    }
+
+    pub fn set_velocity_curve(&mut self, points: &[f32]) {
+        self.velocity.set_custom_curve(points);
+    }
+
+    /// Advances the phases of the voice by one sample at the given frequency and updates its time
+    /// since pressed and time since released fields. Each unison oscillator runs at the frequency
+    /// times its ratio.
+    fn advance(&self, voice: &mut KeyState, frequency: f32, ratios: &[f32], volume: f32) {
+        let dt = 1.0 / self.sample_rate as f32;
+
+        // keeping the phase within 0..1 avoids losing precision on long held notes
+        for (phase, ratio) in voice.phases.iter_mut().zip(ratios) {
+            *phase += frequency * ratio * dt;
+            *phase -= phase.floor();
+        }
+
+        voice.vibrato_phase += self.vibrato_rate * dt;
+        voice.vibrato_phase -= voice.vibrato_phase.floor();
+
+        voice.position_lfo_phase += self.position.lfo_rate() * dt;
+        voice.position_lfo_phase -= voice.position_lfo_phase.floor();
+
+        voice.advance_glide(dt);
+
+        if !voice.is_released {
+            voice.time_since_pressed += dt;
+        } else {
+            voice.time_since_released += dt;
+        }
+
+        if voice.is_stolen {
+            voice.time_since_stolen += dt;
+        }
+
+        voice.last_volume = volume;
+    }
+}
+
+impl Synth for WaveTableSynth {
+    fn sample_rate(&self) -> u32 {
+        return self.sample_rate;
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate.max(1);
+    }
+
+    fn render_voice(&mut self, voice: &mut KeyState, left: &mut [f32], right: &mut [f32]) -> bool {
+        let freq = 440.0 * 2.0f32.powf((voice.pitch + voice.pitch_offset - 69.0) / 12.0);
+
+        let response = self.velocity.response(voice.velocity);
+        let envelope = self.envelope.with_attack_scaled(self.velocity.attack_factor(response));
+
+        // a glide from above needs the level of the higher pitch to stay free of aliasing, and
+        // soft notes may be darkened by reading from a higher level
+        let unison_voices = self.unison.voices();
+        let unison_gain = self.unison.gain();
+        let mut ratios = [1.0; MAX_UNISON];
+        let mut left_gains = [0.0; MAX_UNISON];
+        let mut right_gains = [0.0; MAX_UNISON];
+        for j in 0..unison_voices {
+            ratios[j] = (self.unison.detune(j) / 12.0).exp2();
+            let (left_gain, right_gain) = pan_gains(voice.pan + self.unison.pan(j));
+            left_gains[j] = left_gain * unison_gain;
+            right_gains[j] = right_gain * unison_gain;
+        }
+        let ratios = &ratios[..unison_voices];
+        let highest_ratio = ratios.iter().fold(1.0f32, |a, b| a.max(*b));
+
+        let highest_freq = freq * highest_ratio * (voice.glide_offset.max(0.0) / 12.0).exp2();
+        let level_freq = highest_freq * self.velocity.brightness_factor(response);
+        let level = self.wave_table.level_for_frequency(level_freq, self.sample_rate);
+        let previous_level = self.previous_wave_table.level_for_frequency(level_freq, self.sample_rate);
+
+        for i in 0..left.len() {
+            let (volume, is_active) = envelope.evaluate(voice, response);
+            if !is_active {
+                return false;
+            }
+
+            // the envelope modulates the position by its own shape, without the velocity scaling
+            let envelope_level = if response > 0.0 { volume / response } else { 0.0 };
+            let position = self.position.evaluate(envelope_level, voice.position_lfo_phase, response);
+
+            let crossfade = self.crossfade_amount(i);
+            let previous = if crossfade < 1.0 { Some(&self.previous_wave_table) } else { None };
+
+            for j in 0..unison_voices {
+                let phase = voice.phases[j];
+                let mut value = self.interpolate_frames(&self.wave_table, phase, level, position);
+
+                if let Some(previous) = previous {
+                    let previous_value = self.interpolate_frames(previous, phase, previous_level, position);
+                    value = previous_value + (value - previous_value) * crossfade;
+                }
+
+                left[i] += value * volume * left_gains[j];
+                right[i] += value * volume * right_gains[j];
+            }
+
+            let mut pitch_change = voice.glide_offset;
+            if self.vibrato_depth != 0.0 {
+                let vibrato = (2.0 * std::f32::consts::PI * voice.vibrato_phase).sin();
+                pitch_change += self.vibrato_depth * vibrato;
+            }
+
+            let mut current_freq = freq;
+            if pitch_change != 0.0 {
+                current_freq *= (pitch_change / 12.0).exp2();
+            }
+            self.advance(voice, current_freq, ratios, volume);
+        }
+
+        return true;
+    }
+
+    fn start_voice(&mut self, voice: &mut KeyState) {
+        // a single oscillator always starts at the beginning of the wave, like without unison
+        if self.unison.voices() == 1 {
+            return;
+        }
+
+        let random_phase = self.unison.random_phase();
+        for phase in voice.phases.iter_mut() {
+            *phase = self.rng.next_f32() * random_phase;
+        }
+    }
+
+    fn voice_cost(&self) -> usize {
+        return self.unison.voices();
+    }
+
+    fn render(&mut self, voices: &mut [KeyState], left: &mut [f32], right: &mut [f32]) {
+        self.build_wave_table();
+
+        for voice in voices.iter_mut() {
+            if !voice.is_active {
+                continue;
+            }
+            voice.is_active = self.render_voice(voice, left, right);
+        }
+
+        // all voices share the same crossfade position, so it only advances once per block
+        self.crossfade_elapsed += left.len();
+        if self.crossfade_amount(0) >= 1.0 {
+            self.crossfading = false;
+        }
+    }
+
+    fn set_param(&mut self, param: SynthParam, value: f32) {
+        match param {
+            SynthParam::Interpolation => {
+                if let Some(interpolation) = Interpolation::from_u8(value as u8) {
+                    self.interpolation = interpolation;
+                }
+            }
+            SynthParam::WaveTableCrossfade => self.crossfade_time = value.max(0.0),
+            SynthParam::SytrusShape => self.set_sytrus(SytrusParams { shape: value, ..self.sytrus }),
+            SynthParam::SytrusTension => self.set_sytrus(SytrusParams { tension: value, ..self.sytrus }),
+            SynthParam::SytrusSkew => self.set_sytrus(SytrusParams { skew: value, ..self.sytrus }),
+            SynthParam::SytrusSineShaper => self.set_sytrus(SytrusParams { sine_shaper: value, ..self.sytrus }),
+            SynthParam::SytrusPreFilter => self.set_sytrus(SytrusParams { pre_filter: value, ..self.sytrus }),
+            SynthParam::SytrusShapeEnd => self.set_sytrus(SytrusParams { shape_end: value, ..self.sytrus }),
+            SynthParam::SytrusTensionEnd => self.set_sytrus(SytrusParams { tension_end: value, ..self.sytrus }),
+            SynthParam::SytrusSkewEnd => self.set_sytrus(SytrusParams { skew_end: value, ..self.sytrus }),
+            SynthParam::SytrusSineShaperEnd => self.set_sytrus(SytrusParams { sine_shaper_end: value, ..self.sytrus }),
+            SynthParam::SytrusPreFilterEnd => self.set_sytrus(SytrusParams { pre_filter_end: value, ..self.sytrus }),
+            SynthParam::SytrusFrames => {
+                let frames = (value.round().max(1.0) as usize).min(MAX_SYTRUS_FRAMES);
+                self.set_sytrus(SytrusParams { frames, ..self.sytrus });
+            }
+            SynthParam::VibratoDepth => self.vibrato_depth = value,
+            SynthParam::VibratoRate => self.vibrato_rate = value.max(0.0),
+            _ => {
+                if !self.envelope.set_param(param, value)
+                    && !self.velocity.set_param(param, value)
+                    && !self.unison.set_param(param, value) {
+                    self.position.set_param(param, value);
+                }
+            }
+        }
+    }
+
+    fn set_wave_table(&mut self, wave_table: Vec<f32>, frame_size: usize) {
+        if wave_table.is_empty() {
+            return;
+        }
+
+        // built over the next blocks, taking the place of any Sytrus table waiting to be built
+        self.pending_wave_table = Some(WaveTableSource::Samples(wave_table, frame_size));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sytrus::wave_table_from_sytrus_params;
+
+    /// Renders empty blocks until the synth has generated and swapped in its wave table.
+    fn render_until_generated(synth: &mut WaveTableSynth) {
+        let mut left = [0.0; 128];
+        let mut right = [0.0; 128];
+        for _ in 0..MAX_SYTRUS_FRAMES {
+            synth.render(&mut [], &mut left, &mut right);
+        }
+        assert!(synth.building_wave_table.is_none() && synth.pending_wave_table.is_none());
+    }
+
+    #[test]
+    fn generated_wave_table_matches_the_preview() {
+        // the worker starts on the default parameters, then gets every parameter from the ui
+        let mut synth = WaveTableSynth::new(44100, SytrusParams::default().wave_table(64), Envelope::default());
+        let params = SytrusParams {
+            shape: 0.3,
+            tension: -0.4,
+            skew: 0.6,
+            sine_shaper: -0.2,
+            pre_filter: 0.1,
+            frames: 3,
+            ..SytrusParams::default()
+        };
+        for (param, value) in params.params() {
+            synth.set_param(param, value);
+        }
+        render_until_generated(&mut synth);
+
+        // the graph editor previews the first frame
+        let preview = wave_table_from_sytrus_params(params.shape, params.tension, params.skew,
+                                                    params.sine_shaper, params.pre_filter, 64);
+        let expected = MipMappedTable::new(&preview);
+        let (first, _, _) = synth.wave_table.frames_at(0.0);
+
+        assert_eq!(synth.wave_table.frame_count(), 3);
+        assert_eq!(first.level(0), expected.level(0));
+
+        let wave_table = params.wave_table(64);
+        let expected = MipMappedTable::new(&wave_table[128..]);
+        let (last, _, _) = synth.wave_table.frames_at(1.0);
+        assert_eq!(last.level(0), expected.level(0));
+    }
+
+    #[test]
+    fn empty_wave_tables_are_ignored() {
+        let mut synth = WaveTableSynth::new(44100, vec![0.5; 64], Envelope::default());
+        synth.set_wave_table(Vec::new(), 0);
+        synth.set_wave_table(Vec::new(), 64);
+
+        assert_eq!(synth.wave_table.frame_size(), 64);
+        assert!(!synth.crossfading);
+        assert!(synth.pending_wave_table.is_none());
+    }
+
+    #[test]
+    fn wave_tables_are_built_over_several_blocks() {
+        let mut synth = WaveTableSynth::new(44100, vec![0.0; 64], Envelope::default());
+        let mut left = [0.0; 128];
+        let mut right = [0.0; 128];
+
+        // 8 frames take 4 blocks
+        let samples = SytrusParams { frames: 8, ..SytrusParams::default() }.wave_table(64);
+        synth.set_wave_table(samples.clone(), 64);
+        for _ in 0..3 {
+            synth.render(&mut [], &mut left, &mut right);
+            assert_eq!(synth.wave_table.frame_count(), 1);
+        }
+        synth.render(&mut [], &mut left, &mut right);
+
+        assert_eq!(synth.wave_table.frame_count(), 8);
+        assert!(synth.crossfading);
+        let expected = MipMappedTable::new(&samples[7 * 64..]);
+        let (last, _, _) = synth.wave_table.frames_at(1.0);
+        assert_eq!(last.level(0), expected.level(0));
+    }
+
+    #[test]
+    fn only_the_latest_wave_table_is_built() {
+        let mut synth = WaveTableSynth::new(44100, vec![0.0; 64], Envelope::default());
+        synth.set_wave_table(vec![0.25; 4 * 64], 64);
+        synth.set_wave_table(vec![0.5; 2 * 64], 64);
+        render_until_generated(&mut synth);
+
+        assert_eq!(synth.wave_table.frame_count(), 2);
+        assert_eq!(synth.wave_table.frames_at(0.0).0.level(0)[0], 0.5);
+    }
+
+    #[test]
+    fn frame_size_can_change() {
+        let mut synth = WaveTableSynth::new(44100, vec![0.0; 64], Envelope::default());
+
+        for (value, frame_size) in [(0.25, 128), (0.5, 128), (0.75, 32)] {
+            synth.set_wave_table(vec![value; 2 * frame_size], frame_size);
+            render_until_generated(&mut synth);
+
+            assert_eq!(synth.wave_table.frame_size(), frame_size);
+            assert_eq!(synth.wave_table.frame_count(), 2);
+            assert_eq!(synth.wave_table.frames_at(1.0).0.level(0)[0], value);
+        }
+
+        // Sytrus tables are generated in the frame size of the current table
+        synth.set_param(SynthParam::SytrusShape, 0.5);
+        render_until_generated(&mut synth);
+        assert_eq!(synth.wave_table.frame_size(), 32);
+    }
+}
@@ -0,0 +1,321 @@
+use crate::params::SynthParam;
+use crate::waves::{sawtooth_wave, sin_wave, square_wave, triangle_wave};
+
+/// The highest number of frames generated from Sytrus parameters.
+pub const MAX_SYTRUS_FRAMES: usize = 64;
+
+/// The parameters of a Sytrus style wave shape, each going from -1 to 1.
+///
+/// With more than one frame, every parameter sweeps from its value in the first frame to its end
+/// value in the last one. Voices read between the frames by their wave table position, which the
+/// envelope, the lfo and the velocity modulate per voice, so any of the parameters can be
+/// modulated per voice at audio rate without generating new frames.
+#[derive(Clone, Copy, PartialEq)]
+pub struct SytrusParams {
+    pub shape: f32,
+    pub tension: f32,
+    pub skew: f32,
+    pub sine_shaper: f32,
+    pub pre_filter: f32,
+    /// the values in the last frame, only used with more than one frame
+    pub shape_end: f32,
+    pub tension_end: f32,
+    pub skew_end: f32,
+    pub sine_shaper_end: f32,
+    pub pre_filter_end: f32,
+    /// the number of frames, 0 and 1 both generate a single frame
+    pub frames: usize,
+}
+
+impl Default for SytrusParams {
+    /// A single sawtooth frame. With more frames, the shape sweeps to a pulse.
+    fn default() -> SytrusParams {
+        return SytrusParams {
+            shape: 0.0,
+            tension: 0.0,
+            skew: 0.0,
+            sine_shaper: 0.0,
+            pre_filter: 0.0,
+            shape_end: 1.0,
+            tension_end: 0.0,
+            skew_end: 0.0,
+            sine_shaper_end: 0.0,
+            pre_filter_end: 0.0,
+            frames: 1,
+        };
+    }
+}
+
+impl SytrusParams {
+    /// Returns the synth parameters that make the synth generate these parameters.
+    pub fn params(&self) -> [(SynthParam, f32); 11] {
+        return [
+            (SynthParam::SytrusShape, self.shape),
+            (SynthParam::SytrusTension, self.tension),
+            (SynthParam::SytrusSkew, self.skew),
+            (SynthParam::SytrusSineShaper, self.sine_shaper),
+            (SynthParam::SytrusPreFilter, self.pre_filter),
+            (SynthParam::SytrusShapeEnd, self.shape_end),
+            (SynthParam::SytrusTensionEnd, self.tension_end),
+            (SynthParam::SytrusSkewEnd, self.skew_end),
+            (SynthParam::SytrusSineShaperEnd, self.sine_shaper_end),
+            (SynthParam::SytrusPreFilterEnd, self.pre_filter_end),
+            (SynthParam::SytrusFrames, self.frames as f32),
+        ];
+    }
+
+    /// The number of frames generated, at least one.
+    pub fn frame_count(&self) -> usize {
+        return self.frames.clamp(1, MAX_SYTRUS_FRAMES);
+    }
+
+    /// Fills `table` with the frame at `index`, with every parameter `index / (frames - 1)` of
+    /// the way to its end value.
+    pub fn fill_frame(&self, index: usize, table: &mut [f32]) {
+        let frames = self.frame_count();
+        let t = if frames > 1 { index as f32 / (frames - 1) as f32 } else { 0.0 };
+        let sweep = |start: f32, end: f32| start + (end - start) * t;
+
+        fill_sytrus_table(table, sweep(self.shape, self.shape_end), sweep(self.tension, self.tension_end),
+                          sweep(self.skew, self.skew_end), sweep(self.sine_shaper, self.sine_shaper_end),
+                          sweep(self.pre_filter, self.pre_filter_end));
+    }
+
+    /// Generates the frames of the wave table one after another, each `wave_table_size` samples
+    /// long.
+    pub fn wave_table(&self, wave_table_size: usize) -> Vec<f32> {
+        let mut wave_table = vec![0.0; self.frame_count() * wave_table_size];
+
+        for (i, frame) in wave_table.chunks_mut(wave_table_size.max(1)).enumerate() {
+            self.fill_frame(i, frame);
+        }
+
+        return wave_table;
+    }
+}
+
+/// distorts a value. t is the amount of distortion. from 0 to 1, it distorts, from 0 to -1 it inverts the distortion.
+fn distort(y: f32, t: f32) -> f32 {
+    return dist_value(y.abs(), t) * y.signum();
+}
+
+fn dist_value(y: f32, t: f32) -> f32 {
+    if t > 0.0 && y > 1.0 - t {
+        return 1.0;
+    }
+
+    if t < 0.0 && y < -t {
+        return 0.0;
+    }
+
+    if t == 1.0 {
+        return 1.0;
+    }
+
+    if t == -1.0 {
+        return 0.0;
+    }
+
+    let z = if t > 0.0 {
+        y / (1.0 - t)
+    } else {
+        (y + t) / (t + 1.0)
+    };
+
+    let mut d = 1.0 - t.abs();
+    if t < 0.0 {
+        d = 1.0 / d;
+    }
+
+    return z.powf(d);
+}
+
+/// Returns the position the skewed wave is read at.
+fn skew_position(pos: f32, t: f32) -> f32 {
+    let skew = 0.5 - t * 0.5;
+
+    let x = pos * 2.0 - 1.0;
+    return (x.abs().powf(skew) * x.signum() + 1.0) * 0.5;
+}
+
+/// Returns the position the sine shaped wave is read at.
+fn sin_shape_position(pos: f32, t: f32) -> f32 {
+    let d = 0.5 - t * 0.5;
+
+    let pos_sin = ((pos * 2.0 - 1.0) * std::f32::consts::PI * (0.5 + d)).sin() * 0.5 + 0.5;
+    return (1.0 - d) * pos + d * pos_sin;
+}
+
+fn sawtri(pos: f32, t: f32) -> f32 {
+    let d = 1.0 - t;
+
+    if d == 0.0 {
+        return sawtooth_wave(pos);
+    }
+
+    if pos * 4.0 < d {
+        return pos * 4.0 / d;
+    }
+
+    if pos < 1.0 - d / 4.0 {
+        let a = 2.0 / (d / 2.0 - 1.0);
+        let b = 1.0 / (1.0 - d / 2.0);
+        return a * pos + b;
+    }
+
+    return 4.0 * (pos - 1.0) / d;
+}
+
+fn square_pulse(pos: f32, t: f32) -> f32 {
+    const PULSE_WIDTH: f32 = 0.9;
+
+    let d = 0.5 - t * PULSE_WIDTH * 0.5;
+
+    if pos < d {
+        return 1.0;
+    }
+
+    return -1.0;
+}
+
+/// Applies a kaiser window to the table, the amount from -1 to 1 sets its beta.
+fn filter_table(table: &mut [f32], amount: f32) {
+    let beta = 0.0 + (amount * 0.5 + 0.5) * 10.0;
+
+    let len = table.len();
+    for (i, value) in table.iter_mut().enumerate() {
+        let x = 2.0 * (i as f32) / ((len - 1) as f32) - 1.0;
+        *value *= (1.0 - x.powi(2)).sqrt().powf(beta);
+    }
+}
+
+/// Scales the table to go from -1 to 1, unless it is flat.
+fn normalize_table(table: &mut [f32]) {
+    let mut min = 0.0;
+    let mut max = 0.0;
+
+    for val in table.iter() {
+        if *val < min {
+            min = *val;
+        }
+        if *val > max {
+            max = *val;
+        }
+    }
+
+    if min == max {
+        return;
+    }
+
+    for val in table.iter_mut() {
+        *val = 2.0 * (*val - min) / (max - min) - 1.0;
+    }
+}
+
+/// The basic wave shape, morphing from sine to triangle, sawtooth, square and pulse.
+fn sytrus_shape(pos: f32, shape: f32) -> f32 {
+    if shape < -0.5 {
+        let alpha = shape * 2.0 + 2.0;
+        return sin_wave(pos) * (1.0 - alpha) + triangle_wave(pos) * alpha;
+    } else if shape < 0.0 {
+        let alpha = shape * 2.0 + 1.0;
+        return sawtri(pos, alpha);
+    } else if shape < 0.5 {
+        let alpha = shape * 2.0;
+        return sawtooth_wave(pos) * (1.0 - alpha) + square_wave(pos) * alpha;
+    }
+
+    let alpha = shape * 2.0 - 1.0;
+    return square_pulse(pos, alpha);
+}
+
+/// Fills `table` with one cycle of the wave shaped by the Sytrus parameters. It works in place,
+/// so generating wave tables while playing does not allocate.
+pub fn fill_sytrus_table(table: &mut [f32], shape: f32, tension: f32, skew: f32, sine_shaper: f32, pre_filter: f32) {
+    let size = table.len();
+    for (n, value) in table.iter_mut().enumerate() {
+        let pos = n as f32 / size as f32;
+        let pos = sin_shape_position(skew_position(pos, skew), sine_shaper);
+        *value = distort(sytrus_shape(pos, shape), tension);
+    }
+
+    filter_table(table, pre_filter);
+    normalize_table(table);
+}
+
+pub fn wave_table_from_sytrus_params(shape: f32, tension: f32, skew: f32, sine_shaper: f32, pre_filter: f32, wave_table_size: usize) -> Vec<f32> {
+    let mut table = vec![0.0; wave_table_size];
+    fill_sytrus_table(&mut table, shape, tension, skew, sine_shaper, pre_filter);
+
+    return table;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every parameter from -1 to 1 in steps of 0.5.
+    const STEPS: [f32; 5] = [-1.0, -0.5, 0.0, 0.5, 1.0];
+
+    #[test]
+    fn first_frame_matches_the_preview() {
+        for shape in STEPS {
+            for tension in STEPS {
+                for skew in STEPS {
+                    for sine_shaper in STEPS {
+                        for pre_filter in STEPS {
+                            let params = SytrusParams { shape, tension, skew, sine_shaper, pre_filter, frames: 4, ..SytrusParams::default() };
+                            let preview = wave_table_from_sytrus_params(shape, tension, skew, sine_shaper, pre_filter, 64);
+
+                            assert_eq!(&params.wave_table(64)[..64], &preview[..]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn frames_sweep_every_parameter() {
+        let params = SytrusParams {
+            shape: -1.0,
+            tension: -0.5,
+            skew: 0.0,
+            sine_shaper: 0.5,
+            pre_filter: 1.0,
+            shape_end: 1.0,
+            tension_end: 0.5,
+            skew_end: -1.0,
+            sine_shaper_end: -0.5,
+            pre_filter_end: -1.0,
+            frames: 5,
+        };
+        let wave_table = params.wave_table(64);
+
+        assert_eq!(wave_table.len(), 5 * 64);
+        for (i, frame) in wave_table.chunks(64).enumerate() {
+            let t = i as f32 / 4.0;
+            let sweep = |start: f32, end: f32| start + (end - start) * t;
+            let expected = wave_table_from_sytrus_params(sweep(-1.0, 1.0), sweep(-0.5, 0.5), sweep(0.0, -1.0),
+                                                         sweep(0.5, -0.5), sweep(1.0, -1.0), 64);
+
+            assert_eq!(frame, &expected[..]);
+        }
+    }
+
+    #[test]
+    fn frame_count_is_limited() {
+        assert_eq!(SytrusParams { frames: 0, ..SytrusParams::default() }.frame_count(), 1);
+        assert_eq!(SytrusParams { frames: 1000, ..SytrusParams::default() }.frame_count(), MAX_SYTRUS_FRAMES);
+    }
+
+    #[test]
+    fn tables_are_normalized() {
+        let table = wave_table_from_sytrus_params(0.25, 0.5, -0.5, 0.0, -1.0, 256);
+        let min = table.iter().cloned().fold(f32::MAX, f32::min);
+        let max = table.iter().cloned().fold(f32::MIN, f32::max);
+
+        assert!((min + 1.0).abs() < 1e-6);
+        assert!((max - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,44 @@
+/// The number of MIDI keys.
+pub const KEY_COUNT: usize = 128;
+
+/// Maps every MIDI key to a pitch. Pitches are in semitones on the 12-TET scale, 69 is 440 hz, so
+/// they can be bent and glided in the same way for every tuning.
+pub struct Tuning {
+    /// the pitch of each key, None if the key is not mapped and does not play
+    pitches: [Option<f32>; KEY_COUNT],
+}
+
+impl Tuning {
+    /// 12-TET with the given frequency of A4.
+    pub fn equal_temperament(reference_frequency: f32) -> Tuning {
+        let offset = 12.0 * (reference_frequency / 440.0).log2();
+
+        let mut pitches = [None; KEY_COUNT];
+        for (key, pitch) in pitches.iter_mut().enumerate() {
+            *pitch = Some(key as f32 + offset);
+        }
+
+        return Tuning {
+            pitches,
+        };
+    }
+
+    /// Creates a tuning from the frequency of every key. Keys with a frequency of 0 or below, and
+    /// keys missing at the end, are not mapped.
+    pub fn from_frequencies(frequencies: &[f32]) -> Tuning {
+        let mut pitches = [None; KEY_COUNT];
+        for (pitch, frequency) in pitches.iter_mut().zip(frequencies) {
+            if *frequency > 0.0 && frequency.is_finite() {
+                *pitch = Some(69.0 + 12.0 * (frequency / 440.0).log2());
+            }
+        }
+
+        return Tuning {
+            pitches,
+        };
+    }
+
+    pub fn pitch(&self, key: u8) -> Option<f32> {
+        return self.pitches[key as usize % KEY_COUNT];
+    }
+}
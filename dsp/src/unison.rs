@@ -0,0 +1,86 @@
+use crate::cc_map::Curve;
+use crate::params::SynthParam;
+
+/// The highest number of oscillators stacked on a single note.
+pub const MAX_UNISON: usize = 16;
+
+/// The highest detune in semitones, between the lowest and the highest oscillator.
+const MAX_DETUNE: f32 = 12.0;
+
+/// Stacks several detuned oscillators on every note and spreads them across the stereo field.
+pub struct Unison {
+    /// the number of oscillators per note, from 1 to [MAX_UNISON]
+    voices: usize,
+    /// the distance in semitones between the lowest and the highest oscillator
+    detune: f32,
+    /// how the oscillators are spread between the outermost ones
+    detune_curve: Curve,
+    /// how far the outermost oscillators are panned from the voice, from 0 to 1
+    width: f32,
+    /// how random the start phases of the oscillators are, from 0 for all in phase to 1
+    random_phase: f32,
+}
+
+impl Default for Unison {
+    fn default() -> Unison {
+        return Unison::new();
+    }
+}
+
+impl Unison {
+    /// A single oscillator, which plays like a synth without unison.
+    pub fn new() -> Unison {
+        return Unison {
+            voices: 1,
+            detune: 0.2,
+            detune_curve: Curve::Linear,
+            width: 0.5,
+            random_phase: 1.0,
+        };
+    }
+
+    /// Sets a unison parameter. Returns false if the parameter does not belong to the unison.
+    pub fn set_param(&mut self, param: SynthParam, value: f32) -> bool {
+        match param {
+            SynthParam::UnisonVoices => self.voices = (value.round() as usize).clamp(1, MAX_UNISON),
+            SynthParam::UnisonDetune => self.detune = value.clamp(0.0, MAX_DETUNE),
+            SynthParam::UnisonDetuneCurve => self.detune_curve = Curve::from_u8(value as u8).unwrap_or(self.detune_curve),
+            SynthParam::UnisonWidth => self.width = value.clamp(0.0, 1.0),
+            SynthParam::UnisonRandomPhase => self.random_phase = value.clamp(0.0, 1.0),
+            _ => return false,
+        }
+        return true;
+    }
+
+    pub fn voices(&self) -> usize {
+        return self.voices;
+    }
+
+    pub fn random_phase(&self) -> f32 {
+        return self.random_phase;
+    }
+
+    /// Returns the position of an oscillator within the stack, from -1 to 1.
+    pub fn position(&self, index: usize) -> f32 {
+        if self.voices == 1 {
+            return 0.0;
+        }
+        return 2.0 * index as f32 / (self.voices - 1) as f32 - 1.0;
+    }
+
+    /// Returns the detune of an oscillator in semitones.
+    pub fn detune(&self, index: usize) -> f32 {
+        let position = self.position(index);
+        return self.detune / 2.0 * position.signum() * self.detune_curve.apply(position.abs());
+    }
+
+    /// Returns the pan of an oscillator, relative to the pan of its voice.
+    pub fn pan(&self, index: usize) -> f32 {
+        return self.width * self.position(index);
+    }
+
+    /// Returns the gain of each oscillator, so a stack is about as loud as a single one.
+    pub fn gain(&self) -> f32 {
+        return 1.0 / (self.voices as f32).sqrt();
+    }
+}
@@ -0,0 +1,135 @@
+use crate::cc_map::Curve;
+use crate::params::SynthParam;
+
+/// The number of points of a custom velocity curve, evenly spaced from velocity 0 to 127.
+pub const CUSTOM_CURVE_POINTS: usize = 16;
+
+/// How many mip levels the softest notes are darkened by at full brightness routing. Every level
+/// halves the number of harmonics.
+const BRIGHTNESS_LEVELS: f32 = 4.0;
+
+/// How the velocity of a key maps to the loudness of its note.
+#[derive(Clone, Copy, PartialEq)]
+pub enum VelocityCurve {
+    Linear = 0,
+    /// soft notes are quieter, for keyboards that feel too harsh
+    Exponential = 1,
+    /// soft notes are louder, for keyboards that feel too soft
+    Logarithmic = 2,
+    /// every note is played at full velocity
+    Fixed = 3,
+    /// the curve drawn in the app
+    Custom = 4,
+}
+
+impl VelocityCurve {
+    pub const ALL: [VelocityCurve; 5] = [
+        VelocityCurve::Linear,
+        VelocityCurve::Exponential,
+        VelocityCurve::Logarithmic,
+        VelocityCurve::Fixed,
+        VelocityCurve::Custom,
+    ];
+
+    pub fn from_u8(value: u8) -> Option<VelocityCurve> {
+        return VelocityCurve::ALL.get(value as usize).copied();
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            VelocityCurve::Linear => "Linear",
+            VelocityCurve::Exponential => "Exponential",
+            VelocityCurve::Logarithmic => "Logarithmic",
+            VelocityCurve::Fixed => "Fixed",
+            VelocityCurve::Custom => "Custom",
+        };
+    }
+}
+
+/// Turns key velocities into the response of a patch and routes it to other targets.
+pub struct VelocityResponse {
+    curve: VelocityCurve,
+    custom_curve: [f32; CUSTOM_CURVE_POINTS],
+    /// how much the velocity changes the loudness, from 0 for not at all to 1 for the full curve
+    sensitivity: f32,
+    /// how much softer notes are darkened, from 0 to 1
+    brightness_amount: f32,
+    /// how much harder notes shorten the attack, from 0 to 1
+    attack_amount: f32,
+}
+
+impl Default for VelocityResponse {
+    fn default() -> VelocityResponse {
+        return VelocityResponse::new();
+    }
+}
+
+impl VelocityResponse {
+    /// A linear response at full sensitivity, without routing.
+    pub fn new() -> VelocityResponse {
+        let mut custom_curve = [0.0; CUSTOM_CURVE_POINTS];
+        for (i, point) in custom_curve.iter_mut().enumerate() {
+            *point = i as f32 / (CUSTOM_CURVE_POINTS - 1) as f32;
+        }
+
+        return VelocityResponse {
+            curve: VelocityCurve::Linear,
+            custom_curve,
+            sensitivity: 1.0,
+            brightness_amount: 0.0,
+            attack_amount: 0.0,
+        };
+    }
+
+    /// Sets a velocity parameter. Returns false if the parameter does not belong to the velocity
+    /// response.
+    pub fn set_param(&mut self, param: SynthParam, value: f32) -> bool {
+        match param {
+            SynthParam::VelocityCurve => self.curve = VelocityCurve::from_u8(value as u8).unwrap_or(self.curve),
+            SynthParam::VelocitySensitivity => self.sensitivity = value.clamp(0.0, 1.0),
+            SynthParam::VelocityToBrightness => self.brightness_amount = value.clamp(0.0, 1.0),
+            SynthParam::VelocityToAttack => self.attack_amount = value.clamp(0.0, 1.0),
+            _ => return false,
+        }
+        return true;
+    }
+
+    /// Sets the points of the custom curve, values are clamped to 0 to 1. Missing points keep
+    /// their value.
+    pub fn set_custom_curve(&mut self, points: &[f32]) {
+        for (point, value) in self.custom_curve.iter_mut().zip(points) {
+            *point = value.clamp(0.0, 1.0);
+        }
+    }
+
+    /// Returns the response to a velocity, from 0 to 1.
+    pub fn response(&self, velocity: u8) -> f32 {
+        let x = velocity.min(127) as f32 / 127.0;
+
+        let curved = match self.curve {
+            VelocityCurve::Linear => x,
+            VelocityCurve::Exponential => Curve::Exponential.apply(x),
+            VelocityCurve::Logarithmic => Curve::Logarithmic.apply(x),
+            VelocityCurve::Fixed => 1.0,
+            VelocityCurve::Custom => {
+                let position = x * (CUSTOM_CURVE_POINTS - 1) as f32;
+                let i = (position.floor() as usize).min(CUSTOM_CURVE_POINTS - 2);
+                let t = position - i as f32;
+                self.custom_curve[i] + (self.custom_curve[i + 1] - self.custom_curve[i]) * t
+            }
+        };
+
+        return 1.0 - self.sensitivity * (1.0 - curved);
+    }
+
+    /// Returns the factor the frequency used to pick the mip level is raised by, so softer notes
+    /// are played with fewer harmonics.
+    pub fn brightness_factor(&self, response: f32) -> f32 {
+        return (self.brightness_amount * (1.0 - response) * BRIGHTNESS_LEVELS).exp2();
+    }
+
+    /// Returns the factor the attack time is scaled by.
+    pub fn attack_factor(&self, response: f32) -> f32 {
+        return 1.0 - self.attack_amount * response;
+    }
+}
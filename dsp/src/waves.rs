@@ -17,11 +17,12 @@ pub fn square_wave(pos: f32) -> f32 {
 }
 
 pub fn triangle_wave(pos: f32) -> f32 {
-    return 2.0 * (pos - (pos + 0.5).floor()).abs();
+    return 2.0 * (2.0 * ((pos + 0.25) - (pos + 0.75).floor()).abs()) - 1.0;
 }
 
+/// A falling sawtooth, from 1 at the start of the cycle to -1 at its end.
 pub fn sawtooth_wave(pos: f32) -> f32 {
-    return 2.0 * (pos - (pos + 0.5).floor());
+    return 2.0 * ((pos).floor() - (pos - 0.5));
 }
 
 pub fn lerp_func(a: Box<dyn Wave>, b: Box<dyn Wave>, t: f32) -> Box<dyn Wave> {
@@ -31,6 +32,7 @@ pub fn lerp_func(a: Box<dyn Wave>, b: Box<dyn Wave>, t: f32) -> Box<dyn Wave> {
     Box::new(res)
 }
 
+/// Samples one cycle of the wave, starting at position 0, so the table wraps around seamlessly.
 pub fn wave_table_from_func(wave: Box<dyn Wave>, wave_table_size: usize) -> Vec<f32> {
     let mut wave_table: Vec<f32> = Vec::with_capacity(wave_table_size);
 
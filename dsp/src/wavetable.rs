@@ -0,0 +1,608 @@
+use std::f64::consts::PI;
+
+use crate::params::SynthParam;
+
+/// The number of table samples on each side of the read position used by sinc interpolation.
+const SINC_HALF_WIDTH: i32 = 4;
+
+/// How a wave table is read between two of its samples. Higher order modes sound smoother but
+/// cost more CPU per sample.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Linear = 0,
+    /// 4-point, 3rd order hermite spline
+    Hermite = 1,
+    /// 4-point, 3rd order lagrange polynomial
+    Lagrange = 2,
+    /// sinc with a blackman window, spanning 2 * [SINC_HALF_WIDTH] samples
+    Sinc = 3,
+}
+
+impl Interpolation {
+    pub const ALL: [Interpolation; 4] = [
+        Interpolation::Linear,
+        Interpolation::Hermite,
+        Interpolation::Lagrange,
+        Interpolation::Sinc,
+    ];
+
+    pub fn from_u8(value: u8) -> Option<Interpolation> {
+        return Interpolation::ALL.get(value as usize).copied();
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Interpolation::Linear => "Linear",
+            Interpolation::Hermite => "Cubic Hermite",
+            Interpolation::Lagrange => "Lagrange",
+            Interpolation::Sinc => "Windowed Sinc",
+        };
+    }
+}
+
+/// A single cycle wave table together with band-limited copies of it, one per octave.
+/// Level 0 holds every harmonic the table can represent, each following level holds half as many,
+/// so high notes can be read from a level without harmonics above the nyquist frequency.
+pub struct MipMappedTable {
+    levels: Vec<Vec<f32>>,
+    /// the number of harmonics in level 0
+    harmonics: usize,
+    /// the fourier coefficients of the table, one cosine and sine coefficient per harmonic. They
+    /// are kept so the table can be rebuilt without allocating.
+    cos_coefficients: Vec<f64>,
+    sin_coefficients: Vec<f64>,
+}
+
+impl MipMappedTable {
+    pub fn new(wave_table: &[f32]) -> MipMappedTable {
+        let mut table = MipMappedTable::silent(wave_table.len());
+        table.rebuild(wave_table);
+
+        return table;
+    }
+
+    /// Creates a silent table of `len` samples. Unlike [MipMappedTable::new], this does not run the
+    /// fourier transform, so room for frames that are built later is cheap to make.
+    pub fn silent(len: usize) -> MipMappedTable {
+        let harmonics = len / 2;
+
+        let mut level_count = 0;
+        let mut max_harmonic = harmonics;
+        while max_harmonic >= 1 {
+            level_count += 1;
+            max_harmonic /= 2;
+        }
+
+        return MipMappedTable {
+            levels: vec![vec![0.0; len]; level_count.max(1)],
+            harmonics,
+            cos_coefficients: vec![0.0; harmonics + 1],
+            sin_coefficients: vec![0.0; harmonics + 1],
+        };
+    }
+
+    /// The number of samples in the table.
+    pub fn len(&self) -> usize {
+        return self.levels[0].len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.levels[0].is_empty();
+    }
+
+    /// Replaces the table and all of its levels in place, without allocating. Returns false if
+    /// `wave_table` does not have the size of the table.
+    pub fn rebuild(&mut self, wave_table: &[f32]) -> bool {
+        let n = wave_table.len();
+        if n != self.len() {
+            return false;
+        }
+
+        if self.harmonics == 0 {
+            self.levels[0].copy_from_slice(wave_table);
+            return true;
+        }
+
+        // discrete fourier transform of the table
+        for k in 0..=self.harmonics {
+            let mut cos_coefficient = 0.0;
+            let mut sin_coefficient = 0.0;
+            for (i, x) in wave_table.iter().enumerate() {
+                let angle = 2.0 * PI * (k * i) as f64 / n as f64;
+                cos_coefficient += *x as f64 * angle.cos();
+                sin_coefficient += *x as f64 * angle.sin();
+            }
+
+            // the dc offset and the nyquist harmonic of an even length table only appear once
+            let scale = if k == 0 || 2 * k == n { 1.0 } else { 2.0 };
+            self.cos_coefficients[k] = cos_coefficient * (scale / n as f64);
+            self.sin_coefficients[k] = sin_coefficient * (scale / n as f64);
+        }
+
+        let mut max_harmonic = self.harmonics;
+        for level in self.levels.iter_mut() {
+            for (i, value) in level.iter_mut().enumerate() {
+                let mut sum = self.cos_coefficients[0];
+                for k in 1..=max_harmonic {
+                    let angle = 2.0 * PI * (k * i) as f64 / n as f64;
+                    sum += self.cos_coefficients[k] * angle.cos() + self.sin_coefficients[k] * angle.sin();
+                }
+                *value = sum as f32;
+            }
+
+            max_harmonic /= 2;
+        }
+
+        return true;
+    }
+
+    /// Moves this table `t` of the way towards `other`, in place. Returns false if the two tables
+    /// have different sizes, leaving this table unchanged.
+    pub fn mix_in_place(&mut self, other: &MipMappedTable, t: f32) -> bool {
+        if self.harmonics != other.harmonics || self.levels.len() != other.levels.len() || self.len() != other.len() {
+            return false;
+        }
+
+        for (a, b) in self.levels.iter_mut().zip(other.levels.iter()) {
+            for (a, b) in a.iter_mut().zip(b.iter()) {
+                *a += (b - *a) * t;
+            }
+        }
+
+        return true;
+    }
+
+    pub fn level(&self, index: usize) -> &[f32] {
+        return &self.levels[index.min(self.levels.len() - 1)];
+    }
+
+    /// Returns the level to read from when playing at the given frequency. The fractional part
+    /// can be used to crossfade between the level below and the one above it.
+    ///
+    /// The exact level at which the highest harmonic reaches nyquist is rounded up, so the level
+    /// below is the first one without any harmonic above nyquist, and neither of the two
+    /// crossfaded levels aliases.
+    pub fn level_for_frequency(&self, frequency: f32, sample_rate: u32) -> f32 {
+        let nyquist = sample_rate as f32 / 2.0;
+        let level = (self.harmonics as f32 * frequency / nyquist).log2() + 1.0;
+
+        return level.clamp(0.0, (self.levels.len() - 1) as f32);
+    }
+}
+
+/// A wave table made of several single cycle frames of the same size. Reading it at a position
+/// between two frames interpolates between them, so a table can morph from one shape to another.
+pub struct WaveTable {
+    /// the frames, of which only the first `frame_count` are played. The others are kept so the
+    /// table can be rebuilt with more frames without allocating.
+    frames: Vec<MipMappedTable>,
+    frame_count: usize,
+}
+
+impl WaveTable {
+    /// Splits `samples` into frames of `frame_size` samples. If `samples` cannot be split evenly,
+    /// it is used as a single frame. Empty samples give a single silent sample, as a table has to
+    /// have at least one sample to be read.
+    pub fn new(samples: &[f32], frame_size: usize) -> WaveTable {
+        if samples.is_empty() {
+            return WaveTable::new(&[0.0], 0);
+        }
+
+        let (frame_size, _) = WaveTable::split_frames(samples.len(), frame_size);
+        let frames = samples.chunks(frame_size)
+            .map(MipMappedTable::new)
+            .collect::<Vec<MipMappedTable>>();
+
+        return WaveTable {
+            frame_count: frames.len(),
+            frames,
+        };
+    }
+
+    /// Creates a silent table of a single frame, with room for `frames` frames. None of the frames
+    /// run the fourier transform, see [MipMappedTable::silent].
+    pub fn silent(frame_size: usize, frames: usize) -> WaveTable {
+        return WaveTable {
+            frames: (0..frames.max(1)).map(|_| MipMappedTable::silent(frame_size.max(1))).collect(),
+            frame_count: 1,
+        };
+    }
+
+    /// Returns the size and the number of the frames that `len` samples are split into by
+    /// [WaveTable::new].
+    pub fn split_frames(len: usize, frame_size: usize) -> (usize, usize) {
+        if frame_size == 0 || !len.is_multiple_of(frame_size) {
+            return (len, 1);
+        }
+
+        return (frame_size, len / frame_size);
+    }
+
+    /// The number of samples in each frame.
+    pub fn frame_size(&self) -> usize {
+        return self.frames[0].len();
+    }
+
+    pub fn frame_count(&self) -> usize {
+        return self.frame_count;
+    }
+
+    /// Makes room for at least `frames` frames, so that [WaveTable::rebuild_frame] and
+    /// [WaveTable::set_frame_count] do not need to allocate. This allocates if fewer frames are
+    /// reserved, but the new frames are silent and do not run the fourier transform.
+    pub fn reserve_frames(&mut self, frames: usize) {
+        while self.frames.len() < frames {
+            self.frames.push(MipMappedTable::silent(self.frame_size()));
+        }
+    }
+
+    /// Replaces the frame at `index` in place. Returns false if there is no room for the frame or
+    /// it has the wrong size.
+    pub fn rebuild_frame(&mut self, index: usize, samples: &[f32]) -> bool {
+        return match self.frames.get_mut(index) {
+            Some(frame) => frame.rebuild(samples),
+            None => false,
+        };
+    }
+
+    /// Sets how many of the reserved frames are played. Returns false if fewer are reserved.
+    pub fn set_frame_count(&mut self, frame_count: usize) -> bool {
+        if frame_count == 0 || frame_count > self.frames.len() {
+            return false;
+        }
+
+        self.frame_count = frame_count;
+        return true;
+    }
+
+    /// Returns the two frames around a position from 0 to 1, and how far the position is from the
+    /// first towards the second.
+    pub fn frames_at(&self, position: f32) -> (&MipMappedTable, &MipMappedTable, f32) {
+        let index = position.clamp(0.0, 1.0) * (self.frame_count - 1) as f32;
+        let first = index.floor() as usize;
+        let second = (first + 1).min(self.frame_count - 1);
+
+        return (&self.frames[first], &self.frames[second], index - first as f32);
+    }
+
+    /// Moves this table `t` of the way towards `other`, in place. Returns false if the two tables
+    /// have a different number or size of frames, leaving this table unchanged.
+    pub fn mix_in_place(&mut self, other: &WaveTable, t: f32) -> bool {
+        if self.frame_count != other.frame_count || self.frame_size() != other.frame_size() {
+            return false;
+        }
+
+        for (a, b) in self.frames.iter_mut().zip(other.frames.iter()).take(self.frame_count) {
+            a.mix_in_place(b, t);
+        }
+
+        return true;
+    }
+
+    /// See [MipMappedTable::level_for_frequency], all frames share their levels.
+    pub fn level_for_frequency(&self, frequency: f32, sample_rate: u32) -> f32 {
+        return self.frames[0].level_for_frequency(frequency, sample_rate);
+    }
+}
+
+/// The position within a multi frame wave table, from 0 for the first to 1 for the last frame,
+/// and how it is modulated by the envelope, an lfo and the velocity of a voice.
+pub struct WavePosition {
+    /// the position before modulation
+    position: f32,
+    /// how far the volume envelope moves the position, from -1 to 1
+    envelope_amount: f32,
+    /// how far the lfo moves the position, from 0 to 1
+    lfo_depth: f32,
+    /// the lfo rate in hz
+    lfo_rate: f32,
+    /// how far the velocity response moves the position, from -1 to 1
+    velocity_amount: f32,
+}
+
+impl Default for WavePosition {
+    fn default() -> WavePosition {
+        return WavePosition::new();
+    }
+}
+
+impl WavePosition {
+    /// The first frame, without modulation.
+    pub fn new() -> WavePosition {
+        return WavePosition {
+            position: 0.0,
+            envelope_amount: 0.0,
+            lfo_depth: 0.0,
+            lfo_rate: 1.0,
+            velocity_amount: 0.0,
+        };
+    }
+
+    /// Sets a position parameter. Returns false if the parameter does not belong to the position.
+    pub fn set_param(&mut self, param: SynthParam, value: f32) -> bool {
+        match param {
+            SynthParam::WavePosition => self.position = value.clamp(0.0, 1.0),
+            SynthParam::WavePositionEnvelope => self.envelope_amount = value.clamp(-1.0, 1.0),
+            SynthParam::WavePositionLfoDepth => self.lfo_depth = value.clamp(0.0, 1.0),
+            SynthParam::WavePositionLfoRate => self.lfo_rate = value.max(0.0),
+            SynthParam::WavePositionVelocity => self.velocity_amount = value.clamp(-1.0, 1.0),
+            _ => return false,
+        }
+        return true;
+    }
+
+    pub fn lfo_rate(&self) -> f32 {
+        return self.lfo_rate;
+    }
+
+    /// Returns the modulated position, from 0 to 1.
+    /// param envelope: The level of the volume envelope, from 0 to 1.
+    /// param lfo_phase: The position within the current cycle of the lfo, from 0 to 1.
+    /// param velocity: The velocity response of the voice, from 0 to 1.
+    pub fn evaluate(&self, envelope: f32, lfo_phase: f32, velocity: f32) -> f32 {
+        let mut position = self.position + self.envelope_amount * envelope + self.velocity_amount * velocity;
+        if self.lfo_depth != 0.0 {
+            position += self.lfo_depth * (2.0 * std::f32::consts::PI * lfo_phase).sin();
+        }
+
+        return position.clamp(0.0, 1.0);
+    }
+}
+
+/// Reads a single cycle table at the given phase (from 0 to 1), wrapping around at its end.
+pub fn read_table(table: &[f32], phase: f32, interpolation: Interpolation) -> f32 {
+    let l = table.len() as i32;
+    let index = phase * l as f32;
+    let i = index.floor() as i32;
+    let t = index - i as f32;
+
+    let at = |offset: i32| table[(i + offset).rem_euclid(l) as usize];
+
+    return match interpolation {
+        Interpolation::Linear => {
+            let x0 = at(0);
+            let x1 = at(1);
+            x0 + (x1 - x0) * t
+        }
+        Interpolation::Hermite => {
+            let (xm1, x0, x1, x2) = (at(-1), at(0), at(1), at(2));
+            let c1 = 0.5 * (x1 - xm1);
+            let c2 = xm1 - 2.5 * x0 + 2.0 * x1 - 0.5 * x2;
+            let c3 = 0.5 * (x2 - xm1) + 1.5 * (x0 - x1);
+            ((c3 * t + c2) * t + c1) * t + x0
+        }
+        Interpolation::Lagrange => {
+            let (xm1, x0, x1, x2) = (at(-1), at(0), at(1), at(2));
+            let (tp1, tm1, tm2) = (t + 1.0, t - 1.0, t - 2.0);
+            -xm1 * t * tm1 * tm2 / 6.0
+                + x0 * tp1 * tm1 * tm2 / 2.0
+                - x1 * tp1 * t * tm2 / 2.0
+                + x2 * tp1 * t * tm1 / 6.0
+        }
+        Interpolation::Sinc => {
+            let mut value = 0.0;
+            for offset in (1 - SINC_HALF_WIDTH)..=SINC_HALF_WIDTH {
+                let x = t - offset as f32;
+                value += at(offset) * windowed_sinc(x);
+            }
+            value
+        }
+    };
+}
+
+/// The sinc function multiplied with a blackman window that reaches zero at [SINC_HALF_WIDTH].
+fn windowed_sinc(x: f32) -> f32 {
+    use std::f32::consts::PI;
+
+    if x == 0.0 {
+        return 1.0;
+    }
+
+    let sinc = (PI * x).sin() / (PI * x);
+    let w = PI * (x / SINC_HALF_WIDTH as f32 + 1.0);
+    let window = 0.42 - 0.5 * w.cos() + 0.08 * (2.0 * w).cos();
+
+    return sinc * window;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::waves::sawtooth_wave;
+
+    fn saw(size: usize) -> Vec<f32> {
+        return (0..size).map(|i| sawtooth_wave(i as f32 / size as f32)).collect();
+    }
+
+    /// The amplitude of a harmonic of a single cycle table.
+    fn harmonic_amplitude(table: &[f32], harmonic: usize) -> f64 {
+        let n = table.len();
+        let mut cos_sum = 0.0;
+        let mut sin_sum = 0.0;
+        for (i, x) in table.iter().enumerate() {
+            let angle = 2.0 * PI * (harmonic * i) as f64 / n as f64;
+            cos_sum += *x as f64 * angle.cos();
+            sin_sum += *x as f64 * angle.sin();
+        }
+
+        return (cos_sum * cos_sum + sin_sum * sin_sum).sqrt() * 2.0 / n as f64;
+    }
+
+    /// The highest harmonic a level of a table of the given size holds.
+    fn max_harmonic(size: usize, level: usize) -> usize {
+        return (size / 2) >> level;
+    }
+
+    /// The largest difference between reading a table of a sine and the sine itself, halfway
+    /// between the samples.
+    fn sine_error(size: usize, interpolation: Interpolation) -> f32 {
+        let table = (0..size).map(|i| (2.0 * PI * i as f64 / size as f64).sin() as f32).collect::<Vec<f32>>();
+
+        return (0..size).map(|i| {
+            let phase = (i as f32 + 0.5) / size as f32;
+            let expected = (2.0 * std::f32::consts::PI * phase).sin();
+            (read_table(&table, phase, interpolation) - expected).abs()
+        }).fold(0.0, f32::max);
+    }
+
+    #[test]
+    fn every_interpolation_reads_the_samples() {
+        let samples = saw(16);
+
+        for interpolation in Interpolation::ALL {
+            for (i, sample) in samples.iter().enumerate() {
+                let value = read_table(&samples, i as f32 / 16.0, interpolation);
+                assert!((value - sample).abs() < 1e-5, "{} reads {} at sample {}", interpolation.name(), value, i);
+            }
+        }
+    }
+
+    #[test]
+    fn interpolation_wraps_around_the_end() {
+        let mut samples = vec![0.0; 16];
+        samples[0] = 1.0;
+
+        let value = read_table(&samples, 15.5 / 16.0, Interpolation::Linear);
+        assert_eq!(value, 0.5);
+        assert_eq!(read_table(&samples, -1.0 / 16.0, Interpolation::Linear), 0.0);
+    }
+
+    #[test]
+    fn higher_order_interpolation_is_more_accurate() {
+        let linear = sine_error(32, Interpolation::Linear);
+
+        for interpolation in [Interpolation::Hermite, Interpolation::Lagrange, Interpolation::Sinc] {
+            let error = sine_error(32, interpolation);
+            assert!(error < linear / 4.0, "{} is off by {}, linear by {}", interpolation.name(), error, linear);
+        }
+    }
+
+    #[test]
+    fn every_level_halves_the_harmonics() {
+        let samples = saw(64);
+        let table = MipMappedTable::new(&samples);
+
+        // 32, 16, 8, 4, 2 and 1 harmonics
+        for level in 0..6 {
+            let max_harmonic = max_harmonic(64, level);
+            let samples = table.level(level);
+
+            assert!(harmonic_amplitude(samples, 1) > 0.5);
+            assert!(harmonic_amplitude(samples, max_harmonic) > 1e-3);
+            for harmonic in max_harmonic + 1..=32 {
+                assert!(harmonic_amplitude(samples, harmonic) < 1e-4, "level {} has harmonic {}", level, harmonic);
+            }
+        }
+
+        // the last level is repeated for higher indices
+        assert_eq!(table.level(6), table.level(5));
+    }
+
+    #[test]
+    fn level_zero_is_the_table() {
+        let samples = saw(64);
+        let table = MipMappedTable::new(&samples);
+
+        for (a, b) in table.level(0).iter().zip(samples.iter()) {
+            assert!((a - b).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn level_below_the_frequency_does_not_alias() {
+        let table = MipMappedTable::new(&saw(2048));
+        let sample_rate = 44100;
+        // 1024 down to 1 harmonics
+        let last_level = 10;
+
+        for key in 0..128 {
+            let frequency = 440.0 * ((key as f32 - 69.0) / 12.0).exp2();
+            let level = table.level_for_frequency(frequency, sample_rate).floor() as usize;
+
+            if level < last_level {
+                let highest = max_harmonic(2048, level) as f32 * frequency;
+                assert!(highest <= sample_rate as f32 / 2.0, "key {} reads level {} up to {} hz", key, level, highest);
+            }
+        }
+
+        assert_eq!(table.level_for_frequency(1.0, sample_rate), 0.0);
+        assert_eq!(table.level_for_frequency(sample_rate as f32, sample_rate), last_level as f32);
+    }
+
+    #[test]
+    fn rebuild_matches_new() {
+        let samples = saw(64);
+        let mut table = MipMappedTable::new(&[0.0; 64]);
+
+        assert!(table.rebuild(&samples));
+        assert!(!table.rebuild(&samples[..32]));
+
+        let expected = MipMappedTable::new(&samples);
+        for level in 0..6 {
+            assert_eq!(table.level(level), expected.level(level));
+        }
+    }
+
+    #[test]
+    fn mix_in_place_moves_towards_the_other_table() {
+        let mut table = MipMappedTable::new(&[0.0; 64]);
+        let other = MipMappedTable::new(&saw(64));
+
+        assert!(table.mix_in_place(&other, 0.25));
+        for level in 0..6 {
+            for (a, b) in table.level(level).iter().zip(other.level(level)) {
+                assert!((a - b * 0.25).abs() < 1e-6);
+            }
+        }
+
+        assert!(!table.mix_in_place(&MipMappedTable::new(&[0.0; 32]), 0.5));
+    }
+
+    #[test]
+    fn frames_are_read_by_position() {
+        let mut samples = vec![0.0; 64 * 3];
+        samples[64..128].copy_from_slice(&saw(64));
+        let mut table = WaveTable::new(&samples, 64);
+
+        assert_eq!(table.frame_count(), 3);
+        assert_eq!(table.frame_size(), 64);
+
+        let (first, second, t) = table.frames_at(0.75);
+        assert_eq!(first.level(0), table.frames[1].level(0));
+        assert_eq!(second.level(0), table.frames[2].level(0));
+        assert_eq!(t, 0.5);
+
+        // fewer frames are played without dropping the others
+        assert!(table.set_frame_count(2));
+        let (first, second, t) = table.frames_at(1.0);
+        assert_eq!(first.level(0), second.level(0));
+        assert_eq!(t, 0.0);
+        assert!(table.set_frame_count(3));
+        assert!(!table.set_frame_count(4));
+
+        table.reserve_frames(4);
+        assert!(table.rebuild_frame(3, &saw(64)));
+        assert!(table.set_frame_count(4));
+        assert!(!table.rebuild_frame(4, &saw(64)));
+    }
+
+    #[test]
+    fn empty_samples_are_a_silent_frame() {
+        let table = WaveTable::new(&[], 0);
+        let (frame, _, _) = table.frames_at(0.5);
+
+        assert_eq!(table.frame_count(), 1);
+        assert_eq!(table.frame_size(), 1);
+        for interpolation in Interpolation::ALL {
+            assert_eq!(read_table(frame.level(0), 0.5, interpolation), 0.0);
+        }
+    }
+
+    #[test]
+    fn samples_of_the_wrong_size_are_a_single_frame() {
+        let table = WaveTable::new(&saw(100), 64);
+
+        assert_eq!(table.frame_count(), 1);
+        assert_eq!(table.frame_size(), 100);
+    }
+}
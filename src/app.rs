@@ -1,4 +1,4 @@
-use std::borrow::{Borrow, BorrowMut};
+use std::borrow::Borrow;
 use std::cell::RefCell;
 use std::rc::Rc;
 
@@ -8,9 +8,25 @@ use yew::functional::*;
 use yew::prelude::*;
 
 use crate::audio::manager::Manager;
-use crate::{log, waves};
+use crate::audio::midi::MidiDevice;
+use dsp::midi_parser::MidiEvent;
+use crate::audio::params::{CcMap, CcMapping, Envelope, Interpolation, StealPolicy, SynthParam, SytrusParams};
+use dsp::sytrus::wave_table_from_sytrus_params;
+
+use crate::log;
+use crate::envelope_editor::EnvelopeEditor;
 use crate::handle::{Handle, HandleChangeEvent};
+use crate::midi_devices::MidiDevices;
+use crate::midi_learn::{self, ControlChange, MidiBinding, MidiLearn, MidiLearnView};
 use crate::sytrus::Sytrus;
+use crate::tuning::TuningView;
+use crate::unison_settings::UnisonSettings;
+use crate::velocity_settings::VelocitySettings;
+use crate::voice_settings::VoiceSettings;
+use crate::wave_position::WavePositionSettings;
+use crate::cc_mappings::CcMappingView;
+use crate::learn_select::LearnSelect;
+use crate::sample_rate::{self, SampleRateSettings};
 
 //
 //     return html! {
@@ -28,8 +44,16 @@ pub fn app() -> Html {
 
     let manager = use_state(|| None as Option<Manager>);
 
+    let last_control_change = use_state(|| None as Option<ControlChange>);
+    let control_change_count = use_mut_ref(|| 0u32);
+
+    let midi_devices = use_state(|| Rc::new(Vec::new()));
+
     {
         let mgr_handle = manager.clone();
+        let last_control_change_handle = last_control_change.clone();
+        let midi_devices_handle = midi_devices.clone();
+
         use_effect(move || {
             if mgr_handle.borrow().is_some() {
                 return;
@@ -37,8 +61,20 @@ pub fn app() -> Html {
 
             log!("Initializing...");
 
+            let on_midi_event = Callback::from(move |event: MidiEvent| {
+                if let MidiEvent::ControlChange { channel, controller, value } = event {
+                    let mut count = control_change_count.borrow_mut();
+                    *count += 1;
+                    last_control_change_handle.set(Some(ControlChange { id: *count, channel, controller, value }));
+                }
+            });
+
+            let on_midi_devices_change = Callback::from(move |devices: Vec<MidiDevice>| {
+                midi_devices_handle.set(Rc::new(devices));
+            });
+
             wasm_bindgen_futures::spawn_local(async move {
-                let mgr = Manager::new().await;
+                let mgr = Manager::new(sample_rate::load_sample_rate(), on_midi_event, on_midi_devices_change).await;
                 if mgr.is_err() {
                     console::error_1(&mgr.err().unwrap());
                     return;
@@ -65,38 +101,185 @@ pub fn app() -> Html {
         })
     };
 
-    let wave_table = use_state(|| waves::wave_table_from_func(Box::new(waves::sin), wave_table_size));
+    // the table shown in the graph editor, it is only sent to the worker once it is edited
+    let wave_table = use_state(|| SytrusParams::default().wave_table(wave_table_size));
+
+    let sytrus = use_state(|| None as Option<SytrusParams>);
 
     {
         let mgr_handle = manager.clone();
-        let wave_table_handle = wave_table.clone();
+        let sytrus_handle = sytrus.clone();
 
-        use_effect_with_deps(move |(wt, mgr)| {
-            let m = mgr.borrow().as_ref();
-            if m.is_none() {
-                return;
+        use_effect_with_deps(move |(sytrus, mgr)| {
+            if let (Some(sytrus), Some(mgr)) = (&**sytrus, mgr.borrow().as_ref()) {
+                for (param, value) in sytrus.params() {
+                    mgr.set_param(param, value);
+                }
+            }
+        }, (sytrus_handle, mgr_handle));
+    }
+
+    let envelope = use_state(Envelope::default);
+
+    {
+        let mgr_handle = manager.clone();
+        let envelope_handle = envelope.clone();
+
+        use_effect_with_deps(move |(envelope, mgr)| {
+            if let Some(mgr) = mgr.borrow().as_ref() {
+                mgr.set_envelope(envelope);
+            }
+        }, (envelope_handle, mgr_handle));
+    }
+
+    let on_envelope_change = {
+        let envelope_handle = envelope.clone();
+        Callback::from(move |envelope: Envelope| {
+            envelope_handle.set(envelope);
+        })
+    };
+
+    let tuning = use_state(|| None as Option<Vec<f32>>);
+
+    {
+        let mgr_handle = manager.clone();
+        let tuning_handle = tuning.clone();
+
+        use_effect_with_deps(move |(tuning, mgr)| {
+            if let (Some(tuning), Some(mgr)) = (&**tuning, mgr.borrow().as_ref()) {
+                mgr.set_tuning(tuning.clone());
             }
-            m.unwrap().set_wave_table(wt.iter().map(|x| *x).collect::<Vec<f32>>());
-        }, (wave_table_handle, mgr_handle));
+        }, (tuning_handle, mgr_handle));
     }
 
+    let on_tuning_change = {
+        let tuning_handle = tuning.clone();
+        Callback::from(move |frequencies: Vec<f32>| {
+            tuning_handle.set(Some(frequencies));
+        })
+    };
+
+    // starts out with the mappings the worker starts out with
+    let cc_mappings = use_state(|| {
+        let cc_map = CcMap::new();
+        Rc::new((0..cc_map.len()).map(|i| cc_map.get(i)).collect::<Vec<CcMapping>>())
+    });
+
+    {
+        let mgr_handle = manager.clone();
+        let cc_mappings_handle = cc_mappings.clone();
+
+        use_effect_with_deps(move |(mappings, mgr)| {
+            if let Some(mgr) = mgr.borrow().as_ref() {
+                for mapping in mappings.iter() {
+                    mgr.set_cc_mapping(mapping.channel, mapping.controller, mapping.param, mapping.min, mapping.max, mapping.curve);
+                }
+            }
+
+            // the changed mappings are set from scratch, so the old ones are removed first
+            let mappings = (**mappings).clone();
+            let mgr = mgr.clone();
+            move || {
+                if let Some(mgr) = mgr.borrow().as_ref() {
+                    for mapping in mappings.iter() {
+                        mgr.remove_cc_mapping(mapping.controller);
+                    }
+                }
+            }
+        }, (cc_mappings_handle, mgr_handle));
+    }
+
+    let on_cc_mappings_change = {
+        let cc_mappings_handle = cc_mappings.clone();
+        Callback::from(move |mappings: Vec<CcMapping>| {
+            cc_mappings_handle.set(Rc::new(mappings));
+        })
+    };
+
     let on_handle_change = {
         let wave_table_handle = wave_table.clone();
+        let sytrus_handle = sytrus.clone();
+        let mgr_handle = manager.clone();
 
         Callback::from(move |event: HandleChangeEvent| {
-            let mut wave_table = &*wave_table_handle;
-            wave_table_handle.set(
-                wave_table
-                    .iter()
-                    .enumerate()
-                    .map(|(i, x)| {
-                        if i == event.i {
-                            event.x
-                        } else {
-                            *x
-                        }
+            let wave_table = wave_table_handle
+                .iter()
+                .enumerate()
+                .map(|(i, x)| {
+                    if i == event.i {
+                        event.x
+                    } else {
+                        *x
+                    }
+                })
+                .collect::<Vec<f32>>();
+
+            // the graph editor shows the first frame, so the edit replaces only that frame and
+            // the other frames keep sweeping towards the Sytrus end values
+            if let Some(mgr) = mgr_handle.borrow().as_ref() {
+                let sytrus = sytrus_handle.unwrap_or_default();
+                let mut frames = (0..sytrus.frame_count())
+                    .map(|i| {
+                        let mut frame = vec![0.0; wave_table.len()];
+                        sytrus.fill_frame(i, &mut frame);
+                        frame
                     })
-                    .collect::<Vec<f32>>());
+                    .collect::<Vec<Vec<f32>>>();
+                frames[0] = wave_table.clone();
+
+                mgr.set_wave_table(frames);
+            }
+            wave_table_handle.set(wave_table);
+        })
+    };
+
+    let on_interpolation_change = {
+        let mgr_handle = manager.clone();
+
+        Callback::from(move |index: usize| {
+            if let Some(mgr) = mgr_handle.borrow().as_ref() {
+                mgr.set_param(SynthParam::Interpolation, index as f32);
+            }
+        })
+    };
+
+    let on_param_change = {
+        let mgr_handle = manager.clone();
+
+        Callback::from(move |(param, value): (SynthParam, f32)| {
+            if let Some(mgr) = mgr_handle.borrow().as_ref() {
+                mgr.set_param(param, value);
+            }
+        })
+    };
+
+    let on_velocity_curve_change = {
+        let mgr_handle = manager.clone();
+
+        Callback::from(move |points: Vec<f32>| {
+            if let Some(mgr) = mgr_handle.borrow().as_ref() {
+                mgr.set_velocity_curve(points);
+            }
+        })
+    };
+
+    let on_polyphony_change = {
+        let mgr_handle = manager.clone();
+
+        Callback::from(move |(max_voices, steal_policy): (u32, StealPolicy)| {
+            if let Some(mgr) = mgr_handle.borrow().as_ref() {
+                mgr.set_polyphony(max_voices, steal_policy as u8);
+            }
+        })
+    };
+
+    let on_midi_device_select_change = {
+        let mgr_handle = manager.clone();
+
+        Callback::from(move |(name, selected): (String, bool)| {
+            if let Some(mgr) = mgr_handle.borrow().as_ref() {
+                mgr.set_midi_input_selected(&name, selected);
+            }
         })
     };
 
@@ -124,25 +307,94 @@ pub fn app() -> Html {
 
         move |(i, x)| {
             html! {
-                <Handle x={x} i={i} onchange={on_change.clone()} mouse_down={*mouse_down_ref} />
+                <Handle x={x} i={i} onchange={on_change.clone()} mouse_down={*mouse_down_ref} learn_id={format!("wave-{}", i)} />
             }
         }
     };
 
-    let on_wave_table_change = {
+    let midi_learning = use_state(|| false);
+    let midi_learn_selected = use_state(|| None as Option<String>);
+    let midi_bindings = use_state(|| Rc::new(midi_learn::load_bindings()));
+
+    {
+        let selected_handle = midi_learn_selected.clone();
+        let bindings_handle = midi_bindings.clone();
+
+        // binds the selected control to the next controller that moves
+        use_effect_with_deps(move |control_change| {
+            if let (Some(control_change), Some(control)) = (control_change, &*selected_handle) {
+                let bindings = midi_learn::bind(&bindings_handle, control, control_change.channel, control_change.controller);
+                midi_learn::store_bindings(&bindings);
+                bindings_handle.set(Rc::new(bindings));
+                selected_handle.set(None);
+            }
+        }, *last_control_change);
+    }
+
+    let on_toggle_learning = {
+        let learning_handle = midi_learning.clone();
+        let selected_handle = midi_learn_selected.clone();
+
+        Callback::from(move |_| {
+            learning_handle.set(!*learning_handle);
+            selected_handle.set(None);
+        })
+    };
+
+    let on_bindings_change = {
+        let bindings_handle = midi_bindings.clone();
+
+        Callback::from(move |bindings: Vec<MidiBinding>| {
+            midi_learn::store_bindings(&bindings);
+            bindings_handle.set(Rc::new(bindings));
+        })
+    };
+
+    let midi_learn = MidiLearn {
+        learning: *midi_learning,
+        selected: (*midi_learn_selected).clone(),
+        bindings: (*midi_bindings).clone(),
+        last_control_change: *last_control_change,
+        on_select: {
+            let selected_handle = midi_learn_selected.clone();
+            Callback::from(move |control: String| selected_handle.set(Some(control)))
+        },
+    };
+
+    let on_sytrus_change = {
+        let sytrus_handle = sytrus.clone();
         let wave_table_handle = wave_table.clone();
-        Callback::from(move |wt: Vec<f32>| {
-            wave_table_handle.set(wt);
+
+        Callback::from(move |params: SytrusParams| {
+            // the worker generates the actual table, the graph editor only previews the first frame
+            wave_table_handle.set(wave_table_from_sytrus_params(params.shape, params.tension,
+                params.skew, params.sine_shaper, params.pre_filter, wave_table_size));
+            sytrus_handle.set(Some(params));
         })
     };
 
     html! {
+        <ContextProvider<MidiLearn> context={midi_learn}>
         <main onmousedown={onmousedown} onmouseup={onmouseup}>
         <div class={"graph-editor"}>
         {(*wave_table).iter().enumerate().map(handles).collect::<Html>()}
         </div>
         <button onclick={on_play}>{ "Play" }</button>
-        <Sytrus on_wave_table_change={on_wave_table_change} wave_table_size={wave_table_size} mouse_down={*mouse_down} />
+        <SampleRateSettings sample_rate={(*manager).as_ref().map(|mgr| mgr.sample_rate())} />
+        <LearnSelect options={Interpolation::ALL.iter().map(|interpolation| interpolation.name()).collect::<Vec<&str>>()}
+            learn_id="interpolation" on_select={on_interpolation_change} />
+        <VoiceSettings on_param_change={on_param_change.clone()} on_polyphony_change={on_polyphony_change} />
+        <UnisonSettings on_param_change={on_param_change.clone()} />
+        <VelocitySettings on_param_change={on_param_change.clone()} on_custom_curve_change={on_velocity_curve_change} mouse_down={*mouse_down} />
+        <EnvelopeEditor envelope={*envelope} on_change={on_envelope_change} />
+        <TuningView on_tuning_change={on_tuning_change} />
+        <Sytrus on_change={on_sytrus_change} mouse_down={*mouse_down} />
+        <WavePositionSettings on_param_change={on_param_change} />
+        <MidiDevices devices={(*midi_devices).clone()} on_select_change={on_midi_device_select_change} />
+        <MidiLearnView learning={*midi_learning} bindings={(*midi_bindings).clone()}
+            on_toggle_learning={on_toggle_learning} on_bindings_change={on_bindings_change} />
+        <CcMappingView mappings={(*cc_mappings).clone()} on_mappings_change={on_cc_mappings_change} />
         </main>
+        </ContextProvider<MidiLearn>>
     }
 }
@@ -1,32 +1,71 @@
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 use wasm_bindgen::JsValue;
 use web_sys::console;
+use yew::Callback;
 use crate::audio::master::Master;
 use crate::audio::midi;
-use crate::audio::midi::MidiMessage;
+use crate::audio::midi::{ControlChangeMessage, MidiDevice, MidiInputs, MidiMessage, PitchBendMessage};
+use dsp::midi_parser::MidiEvent;
+use crate::audio::params::{Curve, Envelope, SynthParam};
+
+/// Lets a yew callback be called from the MIDI input handler, which has to be Send. The app runs
+/// on a single thread, so the callback never actually leaves it.
+#[derive(Clone)]
+struct MidiCallback(Callback<MidiEvent>);
+
+impl MidiCallback {
+    fn emit(&self, event: MidiEvent) {
+        self.0.emit(event);
+    }
+}
+
+unsafe impl Send for MidiCallback {
+}
 
 pub struct Manager {
     master: Arc<Mutex<Master>>,
+    midi_inputs: Rc<RefCell<MidiInputs>>,
 }
 
 impl Manager {
-    pub async fn new() -> Result<Manager, JsValue> {
-        let master = Arc::new(Mutex::new(Master::new().await?));
+    /// Creates the audio graph and starts listening to MIDI input. Every received MIDI event is
+    /// also passed to `on_midi_event`, so the app can react to it, and `on_midi_devices_change`
+    /// receives the list of MIDI inputs whenever it changes.
+    pub async fn new(sample_rate: Option<f32>, on_midi_event: Callback<MidiEvent>, on_midi_devices_change: Callback<Vec<MidiDevice>>) -> Result<Manager, JsValue> {
+        let master = Arc::new(Mutex::new(Master::new(sample_rate).await?));
 
-        {
+        let midi_inputs = {
             let master_handle = Arc::clone(&master);
-            midi::setup_listener(move |is_active, note, velocity| {
-                let msg = MidiMessage::new(is_active, note, velocity);
+            let on_midi_event = MidiCallback(on_midi_event);
+            midi::setup_listener(move |stamp, event| {
+                on_midi_event.emit(event.clone());
+
                 let master = master_handle.lock().unwrap();
-                let result = master.post_message(&serde_wasm_bindgen::to_value(&msg).unwrap());
+                let time = master.event_time(stamp);
+
+                let msg = match event {
+                    MidiEvent::NoteOn { channel, note, velocity } =>
+                        serde_wasm_bindgen::to_value(&MidiMessage::new(true, channel, note, velocity, time)),
+                    MidiEvent::NoteOff { channel, note, velocity } =>
+                        serde_wasm_bindgen::to_value(&MidiMessage::new(false, channel, note, velocity, time)),
+                    MidiEvent::PitchBend { channel, value } =>
+                        serde_wasm_bindgen::to_value(&PitchBendMessage::new(channel, value, time)),
+                    MidiEvent::ControlChange { channel, controller, value } =>
+                        serde_wasm_bindgen::to_value(&ControlChangeMessage::new(channel, controller, value, time)),
+                    _ => return,
+                };
+                let result = master.post_message(&msg.unwrap());
                 if result.is_err() {
                     console::error_1(&result.err().unwrap());
                 }
-            });
-        }
+            }, on_midi_devices_change)
+        };
 
         Ok(Self {
             master,
+            midi_inputs,
         })
     }
 
@@ -35,9 +74,55 @@ impl Manager {
         master.play().await
     }
 
-    pub fn set_wave_table(&self, wave_table: Vec<f32>) {
+    pub fn set_wave_table(&self, frames: Vec<Vec<f32>>) {
+        let master = self.master.lock().unwrap();
+        master.set_wave_table(frames);
+    }
+
+    pub fn set_envelope(&self, envelope: &Envelope) {
+        let master = self.master.lock().unwrap();
+        master.set_envelope(envelope);
+    }
+
+    pub fn set_velocity_curve(&self, points: Vec<f32>) {
+        let master = self.master.lock().unwrap();
+        master.set_velocity_curve(points);
+    }
+
+    /// The rate the audio context runs at, which may differ from the requested one.
+    pub fn sample_rate(&self) -> f32 {
         let master = self.master.lock().unwrap();
-        master.set_wave_table(wave_table);
+        master.sample_rate()
+    }
+
+    pub fn set_tuning(&self, frequencies: Vec<f32>) {
+        let master = self.master.lock().unwrap();
+        master.set_tuning(frequencies);
+    }
+
+    pub fn set_polyphony(&self, max_voices: u32, steal_policy: u8) {
+        let master = self.master.lock().unwrap();
+        master.set_polyphony(max_voices, steal_policy);
+    }
+
+    pub fn set_param(&self, param: SynthParam, value: f32) {
+        let master = self.master.lock().unwrap();
+        master.set_param(param, value);
+    }
+
+    pub fn set_cc_mapping(&self, channel: Option<u8>, controller: u8, param: SynthParam, min: f32, max: f32, curve: Curve) {
+        let master = self.master.lock().unwrap();
+        master.set_cc_mapping(channel, controller, param, min, max, curve);
+    }
+
+    pub fn remove_cc_mapping(&self, controller: u8) {
+        let master = self.master.lock().unwrap();
+        master.remove_cc_mapping(controller);
+    }
+
+    /// Turns a MIDI input on or off. Selected inputs are reconnected whenever they are plugged in.
+    pub fn set_midi_input_selected(&self, name: &str, selected: bool) {
+        self.midi_inputs.borrow_mut().set_selected(name, selected);
     }
 
 }
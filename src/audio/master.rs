@@ -1,17 +1,45 @@
+use std::cell::Cell;
+
+use js_sys::{Function, Reflect};
+use serde_derive::Serialize;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{AudioContext, AudioNode, AudioWorkletNode, console, GainNode, Request, Response, Window};
+use web_sys::{AudioContext, AudioContextOptions, AudioNode, AudioWorkletNode, AudioWorkletNodeOptions, console, GainNode, Request, Response, Window};
 
+use crate::audio::params::{Curve, Envelope, SynthParam};
 use crate::log;
 
+/// The time, in seconds, MIDI events are scheduled after the block the worklet renders next, to
+/// cover the time the message takes to reach the worklet.
+const SCHEDULE_MARGIN: f64 = 0.005;
+
+/// How fast the largest measured render ahead time falls back, in seconds per second, so that a
+/// single slow block does not delay all later events.
+const RENDER_AHEAD_DECAY: f64 = 0.01;
+
 pub struct Master {
     audio_context: AudioContext,
     master_processor: AudioWorkletNode,
+    /// how far the rendered audio is ahead of the audio being played, in seconds. It follows the
+    /// largest difference seen and slowly decays from there, so that it does not jitter between
+    /// events.
+    render_ahead: Cell<f64>,
+    /// the context time render_ahead was last updated at
+    render_ahead_time: Cell<f64>,
 }
 
 impl Master {
-    pub async fn new() -> Result<Master, JsValue> {
-        let audio_context = AudioContext::new()?;
+    /// Creates the audio context and the worklet running the synth. If `sample_rate` is given, the
+    /// audio context is asked to run at that rate instead of the device default.
+    pub async fn new(sample_rate: Option<f32>) -> Result<Master, JsValue> {
+        let audio_context = match sample_rate {
+            Some(sample_rate) => {
+                let mut options = AudioContextOptions::new();
+                options.sample_rate(sample_rate);
+                AudioContext::new_with_context_options(&options)?
+            }
+            None => AudioContext::new()?,
+        };
         JsFuture::from(audio_context
             .audio_worklet()?
             .add_module("static/worker/bundled_rust_audio_processor.js")?
@@ -23,7 +51,10 @@ impl Master {
         let gain_node_as_audio_node: &AudioNode = gain_node.as_ref();
         gain_node_as_audio_node.connect_with_audio_node(&audio_context.destination())?;
 
-        let master_processor = AudioWorkletNode::new(&audio_context, "master-processor")?;
+        let mut options = AudioWorkletNodeOptions::new();
+        options.number_of_outputs(1);
+        options.output_channel_count(&js_sys::Array::of1(&JsValue::from_f64(2.0)));
+        let master_processor = AudioWorkletNode::new_with_options(&audio_context, "master-processor", &options)?;
         master_processor.connect_with_audio_node(gain_node_as_audio_node)?;
 
         let master_processor_port = master_processor.port()?;
@@ -31,9 +62,14 @@ impl Master {
         let wasm_module_message = create_message("wasmModule", wasm_module);
         master_processor_port.post_message(&wasm_module_message)?;
 
+        let sample_rate_message = create_message("sampleRate", JsValue::from_f64(audio_context.sample_rate() as f64));
+        master_processor_port.post_message(&sample_rate_message)?;
+
         Ok(Master {
             audio_context,
             master_processor,
+            render_ahead: Cell::new(0.0),
+            render_ahead_time: Cell::new(0.0),
         })
     }
 
@@ -48,17 +84,175 @@ impl Master {
         Ok(())
     }
 
+    /// Converts a MIDI timestamp, in microseconds since the page was loaded, to the audio context
+    /// time at which the worklet should apply the event. Keeping the distance between the events
+    /// instead of applying them when they arrive removes the jitter of the render blocks.
+    pub fn event_time(&self, stamp: u64) -> f64 {
+        let current_time = self.audio_context.current_time();
+
+        let (context_time, performance_time) = match self.output_timestamp() {
+            Some(timestamp) => timestamp,
+            None => return current_time + SCHEDULE_MARGIN,
+        };
+
+        let elapsed = (current_time - self.render_ahead_time.get()).max(0.0);
+        let decayed = (self.render_ahead.get() - RENDER_AHEAD_DECAY * elapsed).max(0.0);
+        let render_ahead = (current_time - context_time).max(decayed);
+        self.render_ahead.set(render_ahead);
+        self.render_ahead_time.set(current_time);
+
+        let time = context_time + (stamp as f64 / 1000.0 - performance_time) / 1000.0;
+        return time + render_ahead + SCHEDULE_MARGIN;
+    }
+
+    /// Returns the context time of the audio being played and the performance time it was played
+    /// at, in seconds and milliseconds. getOutputTimestamp is missing in web-sys and in some
+    /// browsers, so it is looked up by name.
+    fn output_timestamp(&self) -> Option<(f64, f64)> {
+        let get_output_timestamp = Reflect::get(&self.audio_context, &JsValue::from_str("getOutputTimestamp")).ok()?
+            .dyn_into::<Function>().ok()?;
+        let timestamp = get_output_timestamp.call0(&self.audio_context).ok()?;
+
+        let context_time = Reflect::get(&timestamp, &JsValue::from_str("contextTime")).ok()?.as_f64()?;
+        let performance_time = Reflect::get(&timestamp, &JsValue::from_str("performanceTime")).ok()?.as_f64()?;
+
+        // both are 0 until the context starts playing
+        if performance_time == 0.0 {
+            return None;
+        }
+
+        return Some((context_time, performance_time));
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        return self.audio_context.sample_rate();
+    }
+
     pub async fn play(&self) -> Result<(), JsValue> {
         log!("Master play...");
         JsFuture::from(self.audio_context.resume()?).await?;
         Ok(())
     }
 
-    pub fn set_wave_table(&self, wave_table: Vec<f32>) {
+    /// Replaces the wave table with the given frames, which all need to have the same size.
+    pub fn set_wave_table(&self, frames: Vec<Vec<f32>>) {
+        let wave_table = WaveTableSettings {
+            frame_size: frames.first().map_or(0, |frame| frame.len() as u32),
+            samples: frames.concat(),
+        };
         let wave_table_message = create_message("waveTable", serde_wasm_bindgen::to_value(&wave_table).unwrap());
         log!("Master set_wave_table...");
         self.post_message(&wave_table_message).unwrap();
     }
+
+    /// Replaces the whole volume envelope at once.
+    pub fn set_envelope(&self, envelope: &Envelope) {
+        let settings = EnvelopeSettings {
+            delay: envelope.delay,
+            attack: envelope.attack,
+            hold: envelope.hold,
+            decay: envelope.decay,
+            sustain: envelope.sustain,
+            release: envelope.release,
+            attack_curve: envelope.attack_curve as u8,
+            decay_curve: envelope.decay_curve as u8,
+            release_curve: envelope.release_curve as u8,
+            loop_start: envelope.loop_start.map_or(-1, |stage| stage as i8),
+            loop_end: envelope.loop_end as u8,
+        };
+        let envelope_message = create_message("envelope", serde_wasm_bindgen::to_value(&settings).unwrap());
+        self.post_message(&envelope_message).unwrap();
+    }
+
+    /// Sets the points of the custom velocity curve, evenly spaced from velocity 0 to 127.
+    pub fn set_velocity_curve(&self, points: Vec<f32>) {
+        let velocity_curve_message = create_message("velocityCurve", serde_wasm_bindgen::to_value(&points).unwrap());
+        self.post_message(&velocity_curve_message).unwrap();
+    }
+
+    /// Sets the frequency in hz of every MIDI key, 0 for keys that should not play.
+    pub fn set_tuning(&self, frequencies: Vec<f32>) {
+        let tuning_message = create_message("tuning", serde_wasm_bindgen::to_value(&frequencies).unwrap());
+        self.post_message(&tuning_message).unwrap();
+    }
+
+    /// Sets the maximum number of simultaneous voices and the policy used to steal a voice once
+    /// that limit is reached (0 = oldest, 1 = quietest, 2 = same key, 3 = released first).
+    pub fn set_polyphony(&self, max_voices: u32, steal_policy: u8) {
+        let polyphony = PolyphonySettings { max_voices, steal_policy };
+        let polyphony_message = create_message("polyphony", serde_wasm_bindgen::to_value(&polyphony).unwrap());
+        self.post_message(&polyphony_message).unwrap();
+    }
+
+    pub fn set_param(&self, param: SynthParam, value: f32) {
+        let param = ParamChange { param: param as u8, value };
+        let param_message = create_message("param", serde_wasm_bindgen::to_value(&param).unwrap());
+        self.post_message(&param_message).unwrap();
+    }
+
+    /// Routes a controller to a synth parameter, scaling its value from `min` to `max` along the
+    /// curve. If `channel` is None, the mapping listens to all channels.
+    pub fn set_cc_mapping(&self, channel: Option<u8>, controller: u8, param: SynthParam, min: f32, max: f32, curve: Curve) {
+        let mapping = CcMappingSettings {
+            channel: channel.map_or(-1, |c| c as i16),
+            controller,
+            param: param as u8,
+            min,
+            max,
+            curve: curve as u8,
+        };
+        let mapping_message = create_message("ccMapping", serde_wasm_bindgen::to_value(&mapping).unwrap());
+        self.post_message(&mapping_message).unwrap();
+    }
+
+    pub fn remove_cc_mapping(&self, controller: u8) {
+        let mapping_message = create_message("removeCcMapping", JsValue::from_f64(controller as f64));
+        self.post_message(&mapping_message).unwrap();
+    }
+}
+
+#[derive(Serialize)]
+struct WaveTableSettings {
+    samples: Vec<f32>,
+    frame_size: u32,
+}
+
+#[derive(Serialize)]
+struct PolyphonySettings {
+    max_voices: u32,
+    steal_policy: u8,
+}
+
+#[derive(Serialize)]
+struct EnvelopeSettings {
+    delay: f32,
+    attack: f32,
+    hold: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+    attack_curve: u8,
+    decay_curve: u8,
+    release_curve: u8,
+    /// -1 if the envelope does not loop
+    loop_start: i8,
+    loop_end: u8,
+}
+
+#[derive(Serialize)]
+struct CcMappingSettings {
+    channel: i16,
+    controller: u8,
+    param: u8,
+    min: f32,
+    max: f32,
+    curve: u8,
+}
+
+#[derive(Serialize)]
+struct ParamChange {
+    param: u8,
+    value: f32,
 }
 
 unsafe impl Send for Master {
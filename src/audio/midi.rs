@@ -1,98 +1,160 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
-use midir::{Ignore, MidiInput};
+use midir::{Ignore, MidiInput, MidiInputConnection};
 use serde_derive::{Deserialize, Serialize};
-use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen::JsCast;
 use wasm_bindgen::closure::Closure;
 use web_sys::{console};
+use yew::Callback;
 
+use dsp::midi_parser::{MidiEvent, MidiParser};
 use crate::log;
 
-pub(crate) fn setup_listener<F>(on_msg: F)
-    where F: FnMut(bool, u8, u8) + Send + Clone + 'static
-{
-    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+/// How often the available inputs are checked for devices that were plugged in or out.
+const REFRESH_INTERVAL: i32 = 1000;
+
+/// A MIDI input as shown in the device list.
+#[derive(Clone, PartialEq)]
+pub struct MidiDevice {
+    pub name: String,
+    /// whether events of this device are used, it is reconnected whenever it reappears
+    pub selected: bool,
+    /// whether the device is plugged in and its connection is open
+    pub connected: bool,
+}
 
-    let token_outer = Arc::new(Mutex::new(None));
-    let token = token_outer.clone();
-    let closure : Closure<dyn FnMut()> = Closure::wrap(Box::new(move ||{
-        if listen(on_msg.clone()).unwrap() == true {
-            if let Some(token) = *token.lock().unwrap() {
-                web_sys::window().unwrap().clear_interval_with_handle(token);
+/// Keeps a connection open to every selected MIDI input. Devices are identified by their name,
+/// so a selected device that is unplugged is reconnected when it is plugged in again.
+pub(crate) struct MidiInputs {
+    on_msg: Arc<Mutex<Box<dyn FnMut(u64, MidiEvent) + Send>>>,
+    on_devices_change: Callback<Vec<MidiDevice>>,
+    /// every device seen so far, in the order they appeared
+    known: Vec<String>,
+    /// devices the user turned off, all others are selected when they appear
+    deselected: HashSet<String>,
+    /// the devices currently plugged in
+    available: Vec<String>,
+    connections: HashMap<String, MidiInputConnection<()>>,
+}
+
+impl MidiInputs {
+    fn new(on_msg: Box<dyn FnMut(u64, MidiEvent) + Send>, on_devices_change: Callback<Vec<MidiDevice>>) -> MidiInputs {
+        return MidiInputs {
+            on_msg: Arc::new(Mutex::new(on_msg)),
+            on_devices_change,
+            known: Vec::new(),
+            deselected: HashSet::new(),
+            available: Vec::new(),
+            connections: HashMap::new(),
+        };
+    }
+
+    pub fn devices(&self) -> Vec<MidiDevice> {
+        return self.known.iter().map(|name| MidiDevice {
+            name: name.clone(),
+            selected: !self.deselected.contains(name),
+            connected: self.connections.contains_key(name),
+        }).collect();
+    }
+
+    pub fn set_selected(&mut self, name: &str, selected: bool) {
+        if selected {
+            self.deselected.remove(name);
+        } else {
+            self.deselected.insert(name.to_string());
+        }
+
+        if let Err(err) = self.refresh(true) {
+            log!("Could not update MIDI inputs: {}", err);
+        }
+    }
+
+    /// Compares the available inputs with the selected ones, opening and closing connections as
+    /// needed. Reports the device list if anything changed, or if `force_report` is set.
+    fn refresh(&mut self, force_report: bool) -> Result<(), Box<dyn Error>> {
+        let midi_in = MidiInput::new("midir reading input")?;
+        let ports = midi_in.ports();
+        let available = ports.iter()
+            .filter_map(|port| midi_in.port_name(port).ok())
+            .collect::<Vec<String>>();
+
+        let mut changed = available != self.available;
+
+        let closed = self.connections.keys()
+            .filter(|name| !available.contains(name) || self.deselected.contains(*name))
+            .cloned()
+            .collect::<Vec<String>>();
+        for name in closed {
+            if let Some(connection) = self.connections.remove(&name) {
+                connection.close();
+                log!("Closed connection to '{}'", name);
             }
+            changed = true;
         }
-    }));
-    *token_outer.lock().unwrap() = web_sys::window().unwrap().set_interval_with_callback_and_timeout_and_arguments_0(
-        closure.as_ref().unchecked_ref(),
-        200,
-    ).ok();
-    closure.forget();
-}
 
-fn listen<F>(mut on_msg: F) -> Result<bool, Box<dyn Error>>
-    where F: FnMut(bool, u8, u8) + Send + 'static
-{
-    let window = web_sys::window().expect("no global `window` exists");
-
-    let mut midi_in = MidiInput::new("midir reading input")?;
-    midi_in.ignore(Ignore::None);
-
-    // Get an input port
-    let ports = midi_in.ports();
-    let in_port = match &ports[..] {
-        [] => {
-            log!("No ports available yet, will try again");
-            return Ok(false)
-        },
-        [ref port] => {
-            log!("Choosing the only available input port: {}", midi_in.port_name(port).unwrap());
-            port
-        },
-        _ => {
-            let mut msg = "Choose an available input port:\n".to_string();
-            for (i, port) in ports.iter().enumerate() {
-                msg.push_str(format!("{}: {}\n", i, midi_in.port_name(port).unwrap()).as_str());
+        for (port, name) in ports.iter().zip(available.iter()) {
+            if !self.known.contains(name) {
+                self.known.push(name.clone());
+                changed = true;
             }
-            loop {
-                if let Ok(Some(port_str)) = window.prompt_with_message_and_default(&msg, "0") {
-                    if let Ok(port_int) = port_str.parse::<usize>() {
-                        if let Some(port) = &ports.get(port_int) {
-                            break port.clone()
-                        }
-                    }
-                }
+
+            if self.deselected.contains(name) || self.connections.contains_key(name) {
+                continue;
             }
-        }
-    };
 
-    log!("Opening connection");
-    let in_port_name = midi_in.port_name(in_port)?;
+            let mut input = MidiInput::new("midir reading input")?;
+            input.ignore(Ignore::None);
+
+            let on_msg = Arc::clone(&self.on_msg);
+            let mut parser = MidiParser::new();
 
-    // _conn_in needs to be a named parameter, because it needs to be kept alive until the end of the scope
-    let _conn_in = midi_in.connect(in_port, "midir-read-input", move |stamp, message, _| {
-        log!("{}: {:?} (len = {})", stamp, message, message.len());
+            let connection = input.connect(port, "midir-read-input", move |stamp, message, _| {
+                let mut on_msg = on_msg.lock().unwrap();
+                parser.parse(message, |event| on_msg(stamp, event));
+            }, ())?;
 
-        if message.len() != 3 {
-            log!("Invalid message length");
-            return;
+            log!("Connection open, reading input from '{}'", name);
+            self.connections.insert(name.clone(), connection);
+            changed = true;
         }
 
-        let midi_type = message[0] & 0xF0;
-        if midi_type != 0x90 && midi_type != 0x80 {
-            log!("Invalid message type");
-            return;
+        self.available = available;
+
+        if changed || force_report {
+            self.on_devices_change.emit(self.devices());
         }
 
-        let note = message[1];
-        let velocity = message[2];
+        return Ok(());
+    }
+}
 
-        on_msg(midi_type == 0x90, note, velocity);
-    }, ())?;
+/// Starts watching the MIDI inputs. Every event of a selected input is passed to `on_msg` together
+/// with its timestamp, in microseconds since the page was loaded, and `on_devices_change` is called
+/// whenever the device list changes.
+pub(crate) fn setup_listener<F>(on_msg: F, on_devices_change: Callback<Vec<MidiDevice>>) -> Rc<RefCell<MidiInputs>>
+    where F: FnMut(u64, MidiEvent) + Send + 'static
+{
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
 
-    log!("Connection open, reading input from '{}'", in_port_name);
-    Box::leak(Box::new(_conn_in));
-    Ok(true)
+    let inputs = Rc::new(RefCell::new(MidiInputs::new(Box::new(on_msg), on_devices_change)));
+
+    let inputs_handle = Rc::clone(&inputs);
+    let closure : Closure<dyn FnMut()> = Closure::wrap(Box::new(move ||{
+        if let Err(err) = inputs_handle.borrow_mut().refresh(false) {
+            log!("Could not update MIDI inputs: {}", err);
+        }
+    }));
+    web_sys::window().unwrap().set_interval_with_callback_and_timeout_and_arguments_0(
+        closure.as_ref().unchecked_ref(),
+        REFRESH_INTERVAL,
+    ).unwrap();
+    closure.forget();
+
+    return inputs;
 }
 
 #[derive(Serialize, Deserialize)]
@@ -100,17 +162,64 @@ pub(crate) struct MidiMessage {
     #[serde(rename = "type")]
     typ: String,
     is_active: bool,
+    channel: u8,
     note: u8,
     velocity: u8,
+    /// the audio context time the event applies at
+    time: f64,
 }
 
 impl MidiMessage {
-    pub(crate) fn new(is_active: bool, note: u8, velocity: u8) -> Self {
+    pub(crate) fn new(is_active: bool, channel: u8, note: u8, velocity: u8, time: f64) -> Self {
         Self {
             typ: "midi".to_string(),
             is_active,
+            channel,
             note,
             velocity,
+            time,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PitchBendMessage {
+    #[serde(rename = "type")]
+    typ: String,
+    channel: u8,
+    value: i16,
+    time: f64,
+}
+
+impl PitchBendMessage {
+    pub(crate) fn new(channel: u8, value: i16, time: f64) -> Self {
+        Self {
+            typ: "pitchBend".to_string(),
+            channel,
+            value,
+            time,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ControlChangeMessage {
+    #[serde(rename = "type")]
+    typ: String,
+    channel: u8,
+    controller: u8,
+    value: u8,
+    time: f64,
+}
+
+impl ControlChangeMessage {
+    pub(crate) fn new(channel: u8, controller: u8, value: u8, time: f64) -> Self {
+        Self {
+            typ: "controlChange".to_string(),
+            channel,
+            controller,
+            value,
+            time,
         }
     }
 }
@@ -1,3 +1,4 @@
 pub(crate) mod manager;
 mod master;
-mod midi;
+pub(crate) mod midi;
+pub(crate) mod params;
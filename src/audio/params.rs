@@ -0,0 +1,11 @@
+//! The synth parameters and settings the app shares with the audio worker. They come from the dsp
+//! crate, so both sides always agree on how they are numbered.
+
+pub use dsp::bridge::{DEFAULT_MAX_VOICES, DEFAULT_PITCH_BEND_RANGE, DEFAULT_STEAL_POLICY, GlideMode, MAX_VOICES, NotePriority, StealPolicy, VoiceMode};
+pub use dsp::cc_map::{CcMap, CcMapping, Curve};
+pub use dsp::envelope::{Envelope, EnvelopeStage};
+pub use dsp::params::SynthParam;
+pub use dsp::sytrus::{MAX_SYTRUS_FRAMES, SytrusParams};
+pub use dsp::unison::MAX_UNISON;
+pub use dsp::velocity::{CUSTOM_CURVE_POINTS, VelocityCurve};
+pub use dsp::wavetable::Interpolation;
@@ -0,0 +1,129 @@
+use std::rc::Rc;
+
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::{Callback, Html};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::audio::params::{CcMapping, Curve, SynthParam};
+
+/// The mapping added by the add button, the brightness controller moving the wave table position.
+const NEW_MAPPING: CcMapping = CcMapping {
+    channel: None,
+    controller: 74,
+    param: SynthParam::WavePosition,
+    min: 0.0,
+    max: 1.0,
+    curve: Curve::Linear,
+};
+
+#[derive(Properties, PartialEq)]
+pub struct CcMappingViewProps {
+    pub mappings: Rc<Vec<CcMapping>>,
+    pub on_mappings_change: Callback<Vec<CcMapping>>,
+}
+
+/// Lists the controllers routed to synth parameters and lets their range and curve be edited.
+#[function_component(CcMappingView)]
+pub fn cc_mapping_view(props: &CcMappingViewProps) -> Html {
+    let rows = props.mappings.iter().enumerate().map(|(i, mapping)| {
+        let apply_change = {
+            let mappings = props.mappings.clone();
+            let on_change = props.on_mappings_change.clone();
+
+            move |apply: fn(&mut CcMapping, f32), value: String| {
+                if let Ok(value) = value.parse::<f32>() {
+                    let mut mappings = (*mappings).clone();
+                    apply(&mut mappings[i], value);
+                    on_change.emit(mappings);
+                }
+            }
+        };
+
+        let on_input = |apply: fn(&mut CcMapping, f32)| {
+            let apply_change = apply_change.clone();
+
+            Callback::from(move |event: Event| {
+                let input: HtmlInputElement = event.target_unchecked_into();
+                apply_change(apply, input.value());
+            })
+        };
+
+        let on_select = |apply: fn(&mut CcMapping, f32)| {
+            let apply_change = apply_change.clone();
+
+            Callback::from(move |event: Event| {
+                let select: HtmlSelectElement = event.target_unchecked_into();
+                apply_change(apply, select.value());
+            })
+        };
+
+        let on_remove = {
+            let mappings = props.mappings.clone();
+            let on_change = props.on_mappings_change.clone();
+
+            Callback::from(move |_| {
+                let mut mappings = (*mappings).clone();
+                mappings.remove(i);
+                on_change.emit(mappings);
+            })
+        };
+
+        html! {
+            <tr>
+            <td><select onchange={on_select(|mapping, value| mapping.channel = if value < 0.0 { None } else { Some(value as u8) })}>
+                <option value="-1" selected={mapping.channel.is_none()}>{"Any"}</option>
+                {(0..16u8).map(|channel| html! {
+                    <option value={channel.to_string()} selected={mapping.channel == Some(channel)}>{channel + 1}</option>
+                }).collect::<Html>()}
+            </select></td>
+            <td><input type="number" min="0" max="127" value={mapping.controller.to_string()}
+                onchange={on_input(|mapping, value| mapping.controller = value.clamp(0.0, 127.0) as u8)} /></td>
+            <td><select onchange={on_select(|mapping, value| {
+                if let Some(param) = SynthParam::from_u8(value as u8) {
+                    mapping.param = param;
+                }
+            })}>
+                {SynthParam::ALL.iter().map(|param| html! {
+                    <option value={(*param as u8).to_string()} selected={*param == mapping.param}>{param.name()}</option>
+                }).collect::<Html>()}
+            </select></td>
+            <td><input type="number" step="0.01" value={mapping.min.to_string()}
+                onchange={on_input(|mapping, value| mapping.min = value)} /></td>
+            <td><input type="number" step="0.01" value={mapping.max.to_string()}
+                onchange={on_input(|mapping, value| mapping.max = value)} /></td>
+            <td><select onchange={on_select(|mapping, value| {
+                if let Some(curve) = Curve::from_u8(value as u8) {
+                    mapping.curve = curve;
+                }
+            })}>
+                {Curve::ALL.iter().map(|curve| html! {
+                    <option value={(*curve as u8).to_string()} selected={*curve == mapping.curve}>{curve.name()}</option>
+                }).collect::<Html>()}
+            </select></td>
+            <td><button onclick={on_remove}>{"Remove"}</button></td>
+            </tr>
+        }
+    }).collect::<Html>();
+
+    let on_add = {
+        let mappings = props.mappings.clone();
+        let on_change = props.on_mappings_change.clone();
+
+        Callback::from(move |_| {
+            let mut mappings = (*mappings).clone();
+            mappings.push(NEW_MAPPING);
+            on_change.emit(mappings);
+        })
+    };
+
+    return html! {
+        <div class={"cc-mappings"}>
+        <table>
+        <tr><th>{"Channel"}</th><th>{"CC"}</th><th>{"Parameter"}</th><th>{"Min"}</th><th>{"Max"}</th><th>{"Curve"}</th><th></th></tr>
+        {rows}
+        </table>
+        <button onclick={on_add}>{"Add Mapping"}</button>
+        </div>
+    };
+}
@@ -0,0 +1,421 @@
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+use yew::{Callback, Html};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::audio::params::{Curve, Envelope, EnvelopeStage};
+use crate::learn_select::LearnSelect;
+use crate::midi_learn::MidiLearn;
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 200.0;
+
+/// The space above and below the envelope, so the breakpoints and readouts stay visible.
+const PADDING: f64 = 20.0;
+
+/// The time in seconds shown across the width of the editor.
+const TIME_RANGE: f32 = 6.0;
+
+/// The time in seconds the sustain level is drawn for. Its actual length depends on the key.
+const SUSTAIN_TIME: f32 = 0.5;
+
+/// How close in pixels the mouse has to be to a breakpoint to grab it.
+const GRAB_DISTANCE: f64 = 12.0;
+
+/// The number of lines a curved segment is drawn with.
+const CURVE_STEPS: usize = 32;
+
+/// The longest stage time in seconds a controller can set. Short times get more of its range.
+const LEARNED_TIME_RANGE: f32 = 4.0;
+
+/// Identifies the sustain level for MIDI learn. It is selected by clicking the sustain segment.
+const SUSTAIN_LEARN_ID: &str = "envelope-sustain";
+
+/// A point of the envelope that can be dragged.
+#[derive(Clone, Copy, PartialEq)]
+enum Breakpoint {
+    Delay,
+    Attack,
+    Hold,
+    Decay,
+    Release,
+}
+
+impl Breakpoint {
+    const ALL: [Breakpoint; 5] = [
+        Breakpoint::Delay,
+        Breakpoint::Attack,
+        Breakpoint::Hold,
+        Breakpoint::Decay,
+        Breakpoint::Release,
+    ];
+
+    /// Identifies the time of the stage ending at the breakpoint for MIDI learn.
+    fn learn_id(&self) -> &'static str {
+        return match self {
+            Breakpoint::Delay => "envelope-delay",
+            Breakpoint::Attack => "envelope-attack",
+            Breakpoint::Hold => "envelope-hold",
+            Breakpoint::Decay => "envelope-decay",
+            Breakpoint::Release => "envelope-release",
+        };
+    }
+}
+
+/// The MIDI learn classes of the breakpoints and of the sustain level, see [MidiLearn::class].
+#[derive(Clone, Copy, PartialEq, Default)]
+struct LearnClasses {
+    breakpoints: [Option<&'static str>; 5],
+    sustain: Option<&'static str>,
+}
+
+impl LearnClasses {
+    fn new(midi_learn: &Option<MidiLearn>) -> LearnClasses {
+        let midi_learn = match midi_learn {
+            Some(midi_learn) => midi_learn,
+            None => return LearnClasses::default(),
+        };
+
+        return LearnClasses {
+            breakpoints: Breakpoint::ALL.map(|breakpoint| midi_learn.class(breakpoint.learn_id())),
+            sustain: midi_learn.class(SUSTAIN_LEARN_ID),
+        };
+    }
+}
+
+/// Returns the colour a point or segment is drawn in, depending on its MIDI learn class.
+fn learn_color(class: Option<&'static str>, default: &'static str) -> &'static str {
+    return match class {
+        Some("learn-selected") => "#fff6d5",
+        Some("learn-bound") => "orange",
+        _ => default,
+    };
+}
+
+/// Returns the envelope with every breakpoint and the sustain level bound to the last controller
+/// message changed to its value.
+fn apply_learned(envelope: &Envelope, midi_learn: &MidiLearn) -> Envelope {
+    let mut envelope = *envelope;
+
+    for breakpoint in Breakpoint::ALL {
+        if let Some(x) = midi_learn.bound_value(breakpoint.learn_id()) {
+            let time = LEARNED_TIME_RANGE * ((x + 1.0) * 0.5).powi(2);
+            match breakpoint {
+                Breakpoint::Delay => envelope.delay = time,
+                Breakpoint::Attack => envelope.attack = time,
+                Breakpoint::Hold => envelope.hold = time,
+                Breakpoint::Decay => envelope.decay = time,
+                Breakpoint::Release => envelope.release = time,
+            }
+        }
+    }
+
+    if let Some(x) = midi_learn.bound_value(SUSTAIN_LEARN_ID) {
+        envelope.sustain = ((x + 1.0) * 0.5).clamp(0.0, 1.0);
+    }
+
+    return envelope;
+}
+
+/// Returns whether a point is on the sustain segment.
+fn on_sustain(envelope: &Envelope, x: f64, y: f64) -> bool {
+    let start = stage_ends(envelope)[3];
+    return x >= to_x(start) && x <= to_x(start + SUSTAIN_TIME) && (to_y(envelope.sustain) - y).abs() <= GRAB_DISTANCE;
+}
+
+/// Returns the times at which the stages end, in seconds from the key press. The release starts
+/// after the sustain time shown in the editor.
+fn stage_ends(envelope: &Envelope) -> [f32; 5] {
+    let delay = envelope.delay;
+    let attack = delay + envelope.attack;
+    let hold = attack + envelope.hold;
+    let decay = hold + envelope.decay;
+    let release = decay + SUSTAIN_TIME + envelope.release;
+    return [delay, attack, hold, decay, release];
+}
+
+/// Returns the time and level of a breakpoint.
+fn position(envelope: &Envelope, breakpoint: Breakpoint) -> (f32, f32) {
+    let ends = stage_ends(envelope);
+    return match breakpoint {
+        Breakpoint::Delay => (ends[0], 0.0),
+        Breakpoint::Attack => (ends[1], 1.0),
+        Breakpoint::Hold => (ends[2], 1.0),
+        Breakpoint::Decay => (ends[3], envelope.sustain),
+        Breakpoint::Release => (ends[4], 0.0),
+    };
+}
+
+fn to_x(time: f32) -> f64 {
+    return time as f64 / TIME_RANGE as f64 * WIDTH;
+}
+
+fn to_y(level: f32) -> f64 {
+    return PADDING + (1.0 - level as f64) * (HEIGHT - 2.0 * PADDING);
+}
+
+fn to_time(x: f64) -> f32 {
+    return (x / WIDTH * TIME_RANGE as f64).max(0.0) as f32;
+}
+
+fn to_level(y: f64) -> f32 {
+    return (1.0 - (y - PADDING) / (HEIGHT - 2.0 * PADDING)).clamp(0.0, 1.0) as f32;
+}
+
+/// Returns the envelope with a breakpoint moved to the given time and level.
+fn move_breakpoint(envelope: &Envelope, breakpoint: Breakpoint, time: f32, level: f32) -> Envelope {
+    let ends = stage_ends(envelope);
+    let mut envelope = *envelope;
+
+    match breakpoint {
+        Breakpoint::Delay => envelope.delay = time,
+        Breakpoint::Attack => envelope.attack = (time - ends[0]).max(0.0),
+        Breakpoint::Hold => envelope.hold = (time - ends[1]).max(0.0),
+        Breakpoint::Decay => {
+            envelope.decay = (time - ends[2]).max(0.0);
+            envelope.sustain = level;
+        }
+        Breakpoint::Release => envelope.release = (time - ends[3] - SUSTAIN_TIME).max(0.0),
+    }
+
+    return envelope;
+}
+
+/// Returns the readout shown next to a breakpoint.
+fn readout(envelope: &Envelope, breakpoint: Breakpoint) -> String {
+    return match breakpoint {
+        Breakpoint::Delay => format!("D {:.2} s", envelope.delay),
+        Breakpoint::Attack => format!("A {:.2} s", envelope.attack),
+        Breakpoint::Hold => format!("H {:.2} s", envelope.hold),
+        Breakpoint::Decay => format!("D {:.2} s, S {:.0}%", envelope.decay, envelope.sustain * 100.0),
+        Breakpoint::Release => format!("R {:.2} s", envelope.release),
+    };
+}
+
+/// Draws a segment from one level to another, shaped by a curve.
+fn draw_segment(ctx: &CanvasRenderingContext2d, start: f32, end: f32, from: f32, to: f32, curve: Curve) {
+    for step in 1..=CURVE_STEPS {
+        let x = step as f32 / CURVE_STEPS as f32;
+        let level = from + (to - from) * curve.apply(x);
+        ctx.line_to(to_x(start + (end - start) * x), to_y(level));
+    }
+}
+
+fn draw(canvas: &HtmlCanvasElement, envelope: &Envelope, classes: &LearnClasses) -> Result<(), JsValue> {
+    let ctx = canvas
+        .get_context("2d")?
+        .ok_or("no 2d context")?
+        .dyn_into::<CanvasRenderingContext2d>()?;
+
+    ctx.clear_rect(0.0, 0.0, WIDTH, HEIGHT);
+
+    let ends = stage_ends(envelope);
+
+    if let Some(loop_start) = envelope.loop_start {
+        let stage_starts = [0.0, ends[0], ends[1], ends[2]];
+        let start = stage_starts[loop_start as usize];
+        let end = ends[envelope.loop_end as usize];
+        if loop_start <= envelope.loop_end && end > start {
+            ctx.set_fill_style(&JsValue::from_str("rgba(255, 166, 0, 0.2)"));
+            ctx.fill_rect(to_x(start), 0.0, to_x(end) - to_x(start), HEIGHT);
+        }
+    }
+
+    ctx.set_stroke_style(&JsValue::from_str("red"));
+    ctx.set_line_width(2.0);
+    ctx.begin_path();
+    ctx.move_to(to_x(0.0), to_y(0.0));
+    ctx.line_to(to_x(ends[0]), to_y(0.0));
+    draw_segment(&ctx, ends[0], ends[1], 0.0, 1.0, envelope.attack_curve);
+    ctx.line_to(to_x(ends[2]), to_y(1.0));
+    draw_segment(&ctx, ends[2], ends[3], 1.0, envelope.sustain, envelope.decay_curve);
+    ctx.line_to(to_x(ends[3] + SUSTAIN_TIME), to_y(envelope.sustain));
+    draw_segment(&ctx, ends[3] + SUSTAIN_TIME, ends[4], envelope.sustain, 0.0, envelope.release_curve);
+    ctx.stroke();
+
+    if classes.sustain.is_some() {
+        ctx.set_stroke_style(&JsValue::from_str(learn_color(classes.sustain, "red")));
+        ctx.set_line_width(4.0);
+        ctx.begin_path();
+        ctx.move_to(to_x(ends[3]), to_y(envelope.sustain));
+        ctx.line_to(to_x(ends[3] + SUSTAIN_TIME), to_y(envelope.sustain));
+        ctx.stroke();
+    }
+
+    ctx.set_font("12px sans-serif");
+    for (i, breakpoint) in Breakpoint::ALL.iter().enumerate() {
+        let (time, level) = position(envelope, *breakpoint);
+        let (x, y) = (to_x(time), to_y(level));
+
+        ctx.set_fill_style(&JsValue::from_str(learn_color(classes.breakpoints[i], "white")));
+        ctx.begin_path();
+        ctx.arc(x, y, 5.0, 0.0, std::f64::consts::TAU)?;
+        ctx.fill();
+
+        // alternate the readouts above and below the line, so close breakpoints stay readable
+        let text_y = if i % 2 == 0 { y - 8.0 } else { y + 16.0 };
+        ctx.fill_text(&readout(envelope, *breakpoint), x + 6.0, text_y.clamp(12.0, HEIGHT - 4.0))?;
+    }
+
+    return Ok(());
+}
+
+#[derive(Properties, PartialEq)]
+pub struct EnvelopeEditorProps {
+    pub envelope: Envelope,
+    pub on_change: Callback<Envelope>,
+}
+
+/// Draws the volume envelope and lets its breakpoints be dragged. Curves and loop points are
+/// chosen below the graph. While learning, clicking a breakpoint or the sustain segment selects
+/// it for MIDI learn instead.
+#[function_component(EnvelopeEditor)]
+pub fn envelope_editor(props: &EnvelopeEditorProps) -> Html {
+    let canvas_ref = use_node_ref();
+    let dragging = use_state(|| None as Option<Breakpoint>);
+    let midi_learn = use_context::<MidiLearn>();
+    let learn_classes = LearnClasses::new(&midi_learn);
+
+    {
+        let canvas_ref = canvas_ref.clone();
+
+        use_effect_with_deps(move |(envelope, classes)| {
+            if let Some(canvas) = canvas_ref.cast::<HtmlCanvasElement>() {
+                if let Err(err) = draw(&canvas, envelope, classes) {
+                    web_sys::console::error_1(&err);
+                }
+            }
+        }, (props.envelope, learn_classes));
+    }
+
+    {
+        let envelope = props.envelope;
+        let on_change = props.on_change.clone();
+        let last_control_change = midi_learn.as_ref().and_then(|learn| learn.last_control_change);
+        let midi_learn = midi_learn.clone();
+
+        use_effect_with_deps(move |_| {
+            if let Some(midi_learn) = midi_learn {
+                let learned = apply_learned(&envelope, &midi_learn);
+                if learned != envelope {
+                    on_change.emit(learned);
+                }
+            }
+        }, last_control_change);
+    }
+
+    let onmousedown = {
+        let dragging = dragging.clone();
+        let envelope = props.envelope;
+        let midi_learn = midi_learn.clone();
+
+        Callback::from(move |event: MouseEvent| {
+            let (x, y) = (event.offset_x() as f64, event.offset_y() as f64);
+
+            // the last breakpoint wins, so a zero length stage can still be dragged open
+            let grabbed = Breakpoint::ALL.iter().rev().copied().find(|breakpoint| {
+                let (time, level) = position(&envelope, *breakpoint);
+                return (to_x(time) - x).hypot(to_y(level) - y) <= GRAB_DISTANCE;
+            });
+
+            if let Some(midi_learn) = midi_learn.as_ref().filter(|learn| learn.learning) {
+                if let Some(breakpoint) = grabbed {
+                    midi_learn.select(breakpoint.learn_id());
+                } else if on_sustain(&envelope, x, y) {
+                    midi_learn.select(SUSTAIN_LEARN_ID);
+                }
+                return;
+            }
+
+            dragging.set(grabbed);
+        })
+    };
+
+    let onmousemove = {
+        let dragging = dragging.clone();
+        let envelope = props.envelope;
+        let on_change = props.on_change.clone();
+
+        Callback::from(move |event: MouseEvent| {
+            if let Some(breakpoint) = *dragging {
+                let time = to_time(event.offset_x() as f64);
+                let level = to_level(event.offset_y() as f64);
+                on_change.emit(move_breakpoint(&envelope, breakpoint, time, level));
+            }
+        })
+    };
+
+    let onmouseup = {
+        let dragging = dragging.clone();
+
+        Callback::from(move |_: MouseEvent| {
+            dragging.set(None);
+        })
+    };
+
+    let on_curve_change = |set: fn(&mut Envelope, Curve)| {
+        let envelope = props.envelope;
+        let on_change = props.on_change.clone();
+
+        Callback::from(move |index: usize| {
+            if let Some(curve) = Curve::from_u8(index as u8) {
+                let mut envelope = envelope;
+                set(&mut envelope, curve);
+                on_change.emit(envelope);
+            }
+        })
+    };
+
+    let on_loop_start_change = {
+        let envelope = props.envelope;
+        let on_change = props.on_change.clone();
+
+        // the first option turns the loop off
+        Callback::from(move |index: usize| {
+            let loop_start = index.checked_sub(1).and_then(|stage| EnvelopeStage::from_u8(stage as u8));
+            on_change.emit(Envelope { loop_start, ..envelope });
+        })
+    };
+
+    let on_loop_end_change = {
+        let envelope = props.envelope;
+        let on_change = props.on_change.clone();
+
+        Callback::from(move |index: usize| {
+            if let Some(loop_end) = EnvelopeStage::from_u8(index as u8) {
+                on_change.emit(Envelope { loop_end, ..envelope });
+            }
+        })
+    };
+
+    let curve_select = |label: &str, selected: Curve, learn_id: &'static str, on_select: Callback<usize>| html! {
+        <label>{label}
+        <LearnSelect options={Curve::ALL.iter().map(|curve| curve.name()).collect::<Vec<&str>>()}
+            selected={selected as usize} learn_id={learn_id} on_select={on_select} />
+        </label>
+    };
+
+    let stage_names = EnvelopeStage::ALL.iter().map(|stage| stage.name()).collect::<Vec<&str>>();
+
+    let envelope = &props.envelope;
+
+    return html! {
+        <div class={"envelope-editor"}>
+        <canvas ref={canvas_ref} width={WIDTH.to_string()} height={HEIGHT.to_string()}
+            onmousedown={onmousedown} onmousemove={onmousemove} onmouseup={onmouseup.clone()} onmouseleave={onmouseup} />
+        <div>
+        {curve_select("Attack ", envelope.attack_curve, "envelope-attack-curve", on_curve_change(|envelope, curve| envelope.attack_curve = curve))}
+        {curve_select("Decay ", envelope.decay_curve, "envelope-decay-curve", on_curve_change(|envelope, curve| envelope.decay_curve = curve))}
+        {curve_select("Release ", envelope.release_curve, "envelope-release-curve", on_curve_change(|envelope, curve| envelope.release_curve = curve))}
+        <label>{"Loop "}
+        <LearnSelect options={std::iter::once("Off").chain(stage_names.iter().copied()).collect::<Vec<&str>>()}
+            selected={envelope.loop_start.map_or(0, |stage| stage as usize + 1)} learn_id="envelope-loop-start" on_select={on_loop_start_change} />
+        {" to "}
+        <LearnSelect options={stage_names} selected={envelope.loop_end as usize}
+            learn_id="envelope-loop-end" on_select={on_loop_end_change} />
+        </label>
+        </div>
+        </div>
+    };
+}
@@ -3,6 +3,7 @@ use yew::{Callback, Html};
 use yew::functional::*;
 use yew::prelude::*;
 use crate::log;
+use crate::midi_learn::use_midi_learn;
 
 pub struct HandleChangeEvent {
     pub i: usize,
@@ -15,6 +16,9 @@ pub struct HandleProps {
     pub i: usize,
     pub mouse_down: bool,
     pub onchange: Callback<HandleChangeEvent>,
+    /// identifies the handle for MIDI learn, handles without one can't be bound to a controller
+    #[prop_or_default]
+    pub learn_id: Option<String>,
 }
 
 
@@ -28,6 +32,15 @@ pub fn handle(props: &HandleProps) -> Html {
     }
 
     let bounds = use_node_ref();
+    let learn = {
+        let onchange = props.onchange.clone();
+        let i = props.i;
+
+        use_midi_learn(props.learn_id.clone(), Callback::from(move |x: f32| {
+            onchange.emit(HandleChangeEvent { i, x });
+        }))
+    };
+    let learning = learn.learning;
 
     let onmove = {
         let onchange = props.onchange.clone();
@@ -36,7 +49,7 @@ pub fn handle(props: &HandleProps) -> Html {
         let mouse_down = props.mouse_down;
 
         Callback::from(move |event: MouseEvent| {
-            if !mouse_down {
+            if !mouse_down || learning {
                 return;
             }
 
@@ -52,7 +65,7 @@ pub fn handle(props: &HandleProps) -> Html {
     };
 
     return html! {
-        <div class={"graph-handle"} onmousemove={onmove} ref={bounds}>
+        <div class={classes!("graph-handle", learn.class)} onmousemove={onmove} onclick={learn.onclick} ref={bounds}>
         <div class={"graph-handle-indicator"} style={format!("height: {}px; margin-top: {}px", height, top)}>
         </div>
         </div>
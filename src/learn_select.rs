@@ -0,0 +1,79 @@
+use web_sys::HtmlSelectElement;
+use yew::{Callback, Html};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::midi_learn::use_midi_learn;
+
+#[derive(Properties, PartialEq)]
+pub struct LearnSelectProps {
+    /// the names of the options
+    pub options: Vec<&'static str>,
+    /// the index of the option selected at first, and whenever it changes
+    #[prop_or_default]
+    pub selected: usize,
+    /// identifies the select for MIDI learn
+    pub learn_id: AttrValue,
+    /// called with the index of the chosen option
+    pub on_select: Callback<usize>,
+}
+
+/// A select whose option can also be chosen by a controller bound to it with MIDI learn. The
+/// range of the controller is split evenly between the options.
+#[function_component(LearnSelect)]
+pub fn learn_select(props: &LearnSelectProps) -> Html {
+    let selected = use_state(|| props.selected);
+
+    {
+        let selected = selected.clone();
+
+        use_effect_with_deps(move |value| {
+            selected.set(*value);
+        }, props.selected);
+    }
+
+    let choose = {
+        let selected = selected.clone();
+        let on_select = props.on_select.clone();
+
+        Callback::from(move |index: usize| {
+            if index != *selected {
+                selected.set(index);
+                on_select.emit(index);
+            }
+        })
+    };
+
+    let learn = {
+        let option_count = props.options.len();
+        let choose = choose.clone();
+
+        use_midi_learn(Some(props.learn_id.to_string()), Callback::from(move |x: f32| {
+            let index = ((x + 1.0) * 0.5 * option_count as f32) as usize;
+            choose.emit(index.min(option_count.saturating_sub(1)));
+        }))
+    };
+
+    let on_change = {
+        let learning = learn.learning;
+
+        Callback::from(move |event: Event| {
+            if learning {
+                return;
+            }
+
+            let select: HtmlSelectElement = event.target_unchecked_into();
+            if let Ok(index) = select.value().parse::<usize>() {
+                choose.emit(index);
+            }
+        })
+    };
+
+    return html! {
+        <select class={classes!(learn.class)} onclick={learn.onclick} onchange={on_change}>
+        {props.options.iter().enumerate().map(|(i, name)| html! {
+            <option value={i.to_string()} selected={i == *selected}>{*name}</option>
+        }).collect::<Html>()}
+        </select>
+    };
+}
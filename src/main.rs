@@ -1,9 +1,20 @@
 mod app;
 mod log;
 mod audio;
-mod waves;
 mod sytrus;
 mod handle;
+mod midi_learn;
+mod midi_devices;
+mod voice_settings;
+mod tuning;
+mod envelope_editor;
+mod velocity_settings;
+mod unison_settings;
+mod wave_position;
+mod cc_mappings;
+mod param_slider;
+mod learn_select;
+mod sample_rate;
 
 use app::App;
 
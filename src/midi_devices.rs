@@ -0,0 +1,58 @@
+use std::rc::Rc;
+
+use web_sys::HtmlInputElement;
+use yew::{Callback, Html};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::audio::midi::MidiDevice;
+
+#[derive(Properties, PartialEq)]
+pub struct MidiDevicesProps {
+    pub devices: Rc<Vec<MidiDevice>>,
+    /// called with the device name and whether it should be used
+    pub on_select_change: Callback<(String, bool)>,
+}
+
+/// Lists all MIDI inputs seen so far and lets any number of them be used at once.
+#[function_component(MidiDevices)]
+pub fn midi_devices(props: &MidiDevicesProps) -> Html {
+    let rows = props.devices.iter().map(|device| {
+        let on_change = {
+            let name = device.name.clone();
+            let on_select_change = props.on_select_change.clone();
+
+            Callback::from(move |event: Event| {
+                let input: HtmlInputElement = event.target_unchecked_into();
+                on_select_change.emit((name.clone(), input.checked()));
+            })
+        };
+
+        let status = match (device.selected, device.connected) {
+            (true, true) => "connected",
+            (true, false) => "waiting for device",
+            (false, _) => "off",
+        };
+
+        html! {
+            <li>
+            <label>
+            <input type="checkbox" checked={device.selected} onchange={on_change} />
+            {&device.name}
+            </label>
+            <span class={classes!("midi-device-status", device.connected.then_some("connected"))}>{status}</span>
+            </li>
+        }
+    }).collect::<Html>();
+
+    return html! {
+        <div class={"midi-devices"}>
+        <h3>{"MIDI Inputs"}</h3>
+        if props.devices.is_empty() {
+            <p>{"No MIDI inputs found. Plug in a device, it will show up here."}</p>
+        } else {
+            <ul>{rows}</ul>
+        }
+        </div>
+    };
+}
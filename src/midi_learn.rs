@@ -0,0 +1,246 @@
+use std::rc::Rc;
+
+use gloo::storage::{LocalStorage, Storage};
+use serde_derive::{Deserialize, Serialize};
+use web_sys::HtmlInputElement;
+use yew::{Callback, Html};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::log;
+
+/// The local storage key the bindings are persisted under.
+const STORAGE_KEY: &str = "midi_learn_bindings";
+
+/// Binds a hardware controller to an on-screen control.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct MidiBinding {
+    /// the `learn_id` of the bound control
+    pub control: String,
+    pub channel: u8,
+    pub controller: u8,
+}
+
+/// A controller message received from a MIDI input.
+#[derive(Clone, Copy, PartialEq)]
+pub struct ControlChange {
+    /// increases with every message, so that repeated values are still noticed
+    pub id: u32,
+    pub channel: u8,
+    pub controller: u8,
+    pub value: u8,
+}
+
+/// The MIDI learn state shared with all controls.
+#[derive(Clone, PartialEq)]
+pub struct MidiLearn {
+    /// whether clicking a control selects it for learning instead of changing it
+    pub learning: bool,
+    /// the control that is bound to the next controller that moves
+    pub selected: Option<String>,
+    pub bindings: Rc<Vec<MidiBinding>>,
+    pub last_control_change: Option<ControlChange>,
+    pub on_select: Callback<String>,
+}
+
+impl MidiLearn {
+    /// Returns the control value from -1 to 1 if the controller message is bound to the control.
+    pub fn value_for(&self, control: &str, control_change: &ControlChange) -> Option<f32> {
+        let is_bound = self.bindings.iter().any(|binding| {
+            binding.control == control
+                && binding.channel == control_change.channel
+                && binding.controller == control_change.controller
+        });
+
+        if !is_bound {
+            return None;
+        }
+
+        return Some(control_change.value as f32 / 127.0 * 2.0 - 1.0);
+    }
+
+    pub fn is_bound(&self, control: &str) -> bool {
+        return self.bindings.iter().any(|binding| binding.control == control);
+    }
+
+    /// Returns the value from -1 to 1 of the last controller message if it is bound to the
+    /// control. While learning, moving controllers are being bound and do not change controls.
+    pub fn bound_value(&self, control: &str) -> Option<f32> {
+        if self.learning {
+            return None;
+        }
+
+        return self.value_for(control, self.last_control_change.as_ref()?);
+    }
+
+    /// Selects the control to be bound to the next controller that moves, if learning.
+    pub fn select(&self, control: &str) {
+        if self.learning {
+            self.on_select.emit(control.to_string());
+        }
+    }
+
+    /// Returns the class marking a control as selected for learning or bound to a controller.
+    pub fn class(&self, control: &str) -> Option<&'static str> {
+        if self.selected.as_deref() == Some(control) {
+            return Some("learn-selected");
+        }
+
+        if self.is_bound(control) {
+            return Some("learn-bound");
+        }
+
+        return None;
+    }
+}
+
+/// What a control needs to take part in MIDI learn, see [use_midi_learn].
+pub struct LearnState {
+    /// whether clicking the control selects it for learning instead of changing it
+    pub learning: bool,
+    pub onclick: Callback<MouseEvent>,
+    /// marks the control as selected for learning or bound to a controller
+    pub class: Option<&'static str>,
+}
+
+/// Lets a control be bound to a controller. `on_value` is called with the value from -1 to 1
+/// whenever a controller bound to the control moves. Controls without a `learn_id` can't be bound.
+#[hook]
+pub fn use_midi_learn(learn_id: Option<String>, on_value: Callback<f32>) -> LearnState {
+    let midi_learn = use_context::<MidiLearn>();
+
+    {
+        let learn_id = learn_id.clone();
+        let last_control_change = midi_learn.as_ref().and_then(|learn| learn.last_control_change);
+        let midi_learn = midi_learn.clone();
+
+        use_effect_with_deps(move |_| {
+            if let (Some(learn_id), Some(midi_learn)) = (learn_id, midi_learn) {
+                if let Some(value) = midi_learn.bound_value(&learn_id) {
+                    on_value.emit(value);
+                }
+            }
+        }, last_control_change);
+    }
+
+    let onclick = {
+        let learn_id = learn_id.clone();
+        let midi_learn = midi_learn.clone();
+
+        Callback::from(move |_: MouseEvent| {
+            if let (Some(learn_id), Some(midi_learn)) = (&learn_id, &midi_learn) {
+                midi_learn.select(learn_id);
+            }
+        })
+    };
+
+    let class = match (&learn_id, &midi_learn) {
+        (Some(learn_id), Some(midi_learn)) => midi_learn.class(learn_id),
+        _ => None,
+    };
+
+    return LearnState {
+        learning: midi_learn.as_ref().map_or(false, |learn| learn.learning),
+        onclick,
+        class,
+    };
+}
+
+pub fn load_bindings() -> Vec<MidiBinding> {
+    return LocalStorage::get(STORAGE_KEY).unwrap_or_default();
+}
+
+pub fn store_bindings(bindings: &Vec<MidiBinding>) {
+    if let Err(err) = LocalStorage::set(STORAGE_KEY, bindings) {
+        log!("Could not store MIDI bindings: {}", err);
+    }
+}
+
+/// Returns the bindings with the control bound to the given controller, replacing its old binding.
+pub fn bind(bindings: &Vec<MidiBinding>, control: &str, channel: u8, controller: u8) -> Vec<MidiBinding> {
+    let mut bindings = bindings.iter()
+        .filter(|binding| binding.control != control)
+        .cloned()
+        .collect::<Vec<MidiBinding>>();
+
+    bindings.push(MidiBinding {
+        control: control.to_string(),
+        channel,
+        controller,
+    });
+
+    return bindings;
+}
+
+#[derive(Properties, PartialEq)]
+pub struct MidiLearnViewProps {
+    pub learning: bool,
+    pub bindings: Rc<Vec<MidiBinding>>,
+    pub on_toggle_learning: Callback<()>,
+    pub on_bindings_change: Callback<Vec<MidiBinding>>,
+}
+
+/// Lists all bindings and lets them be edited or removed.
+#[function_component(MidiLearnView)]
+pub fn midi_learn_view(props: &MidiLearnViewProps) -> Html {
+    let on_toggle = {
+        let on_toggle_learning = props.on_toggle_learning.clone();
+
+        Callback::from(move |_| {
+            on_toggle_learning.emit(());
+        })
+    };
+
+    let rows = props.bindings.iter().enumerate().map(|(i, binding)| {
+        let update = {
+            let bindings = props.bindings.clone();
+            let on_change = props.on_bindings_change.clone();
+
+            move |apply: fn(&mut MidiBinding, u8)| {
+                let bindings = bindings.clone();
+                let on_change = on_change.clone();
+
+                Callback::from(move |event: Event| {
+                    let input: HtmlInputElement = event.target_unchecked_into();
+                    if let Ok(value) = input.value().parse::<u8>() {
+                        let mut bindings = (*bindings).clone();
+                        apply(&mut bindings[i], value);
+                        on_change.emit(bindings);
+                    }
+                })
+            }
+        };
+
+        let on_remove = {
+            let bindings = props.bindings.clone();
+            let on_change = props.on_bindings_change.clone();
+
+            Callback::from(move |_| {
+                let mut bindings = (*bindings).clone();
+                bindings.remove(i);
+                on_change.emit(bindings);
+            })
+        };
+
+        html! {
+            <tr>
+            <td>{&binding.control}</td>
+            <td><input type="number" min="0" max="15" value={binding.channel.to_string()}
+                onchange={update(|binding, value| binding.channel = value.min(15))} /></td>
+            <td><input type="number" min="0" max="127" value={binding.controller.to_string()}
+                onchange={update(|binding, value| binding.controller = value.min(127))} /></td>
+            <td><button onclick={on_remove}>{"Remove"}</button></td>
+            </tr>
+        }
+    }).collect::<Html>();
+
+    return html! {
+        <div class={"midi-learn"}>
+        <button onclick={on_toggle}>{ if props.learning { "Stop MIDI Learn" } else { "MIDI Learn" } }</button>
+        <table>
+        <tr><th>{"Control"}</th><th>{"Channel"}</th><th>{"CC"}</th><th></th></tr>
+        {rows}
+        </table>
+        </div>
+    };
+}
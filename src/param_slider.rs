@@ -0,0 +1,69 @@
+use web_sys::HtmlInputElement;
+use yew::{Callback, Html};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::audio::params::SynthParam;
+use crate::midi_learn::use_midi_learn;
+
+#[derive(Properties, PartialEq)]
+pub struct ParamSliderProps {
+    pub label: AttrValue,
+    pub param: SynthParam,
+    pub min: f32,
+    pub max: f32,
+    /// the value the slider starts at
+    pub value: f32,
+    /// identifies the slider for MIDI learn
+    pub learn_id: AttrValue,
+    pub on_param_change: Callback<(SynthParam, f32)>,
+}
+
+/// A labelled slider that sends every change of a synth parameter. A controller bound to it with
+/// MIDI learn moves it across its whole range.
+#[function_component(ParamSlider)]
+pub fn param_slider(props: &ParamSliderProps) -> Html {
+    let value = use_state(|| props.value);
+
+    let set_value = {
+        let value = value.clone();
+        let param = props.param;
+        let on_param_change = props.on_param_change.clone();
+
+        Callback::from(move |new_value: f32| {
+            value.set(new_value);
+            on_param_change.emit((param, new_value));
+        })
+    };
+
+    let learn = {
+        let (min, max) = (props.min, props.max);
+        let set_value = set_value.clone();
+
+        use_midi_learn(Some(props.learn_id.to_string()), Callback::from(move |x: f32| {
+            set_value.emit(min + (x + 1.0) * 0.5 * (max - min));
+        }))
+    };
+
+    let on_input = {
+        let learning = learn.learning;
+
+        Callback::from(move |event: InputEvent| {
+            if learning {
+                return;
+            }
+
+            let input: HtmlInputElement = event.target_unchecked_into();
+            if let Ok(value) = input.value().parse::<f32>() {
+                set_value.emit(value);
+            }
+        })
+    };
+
+    return html! {
+        <label class={classes!(learn.class)} onclick={learn.onclick}>{&props.label}
+        <input type="range" min={props.min.to_string()} max={props.max.to_string()} step="0.01"
+            value={value.to_string()} oninput={on_input} />
+        </label>
+    };
+}
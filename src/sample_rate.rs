@@ -0,0 +1,66 @@
+use gloo::storage::{LocalStorage, Storage};
+use web_sys::HtmlSelectElement;
+use yew::{Callback, Html};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::log;
+
+const STORAGE_KEY: &str = "sample-rate";
+
+/// The rates the audio context can be asked to run at, besides the default of the audio device.
+const SAMPLE_RATES: [u32; 4] = [44100, 48000, 88200, 96000];
+
+/// Returns the stored sample rate the audio context should run at, None for the device default.
+pub fn load_sample_rate() -> Option<f32> {
+    return LocalStorage::get::<u32>(STORAGE_KEY).ok().map(|sample_rate| sample_rate as f32);
+}
+
+fn store_sample_rate(sample_rate: Option<u32>) {
+    match sample_rate {
+        Some(sample_rate) => {
+            if let Err(err) = LocalStorage::set(STORAGE_KEY, sample_rate) {
+                log!("Could not store the sample rate: {}", err);
+            }
+        }
+        None => LocalStorage::delete(STORAGE_KEY),
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct SampleRateSettingsProps {
+    /// the rate the audio context runs at, None until it is created
+    pub sample_rate: Option<f32>,
+}
+
+/// Lets the sample rate of the audio context be chosen. The rate of an audio context is fixed when
+/// it is created, so the choice is stored and the page is reloaded to apply it.
+#[function_component(SampleRateSettings)]
+pub fn sample_rate_settings(props: &SampleRateSettingsProps) -> Html {
+    let requested = load_sample_rate().map(|sample_rate| sample_rate as u32);
+
+    let on_change = Callback::from(|event: Event| {
+        let select: HtmlSelectElement = event.target_unchecked_into();
+        store_sample_rate(select.value().parse::<u32>().ok());
+
+        if let Err(err) = web_sys::window().unwrap().location().reload() {
+            log!("Could not reload the page: {:?}", err);
+        }
+    });
+
+    return html! {
+        <div class={"sample-rate"}>
+        <label>{"Sample Rate "}
+        <select onchange={on_change}>
+            <option value="" selected={requested.is_none()}>{"Device Default"}</option>
+            {SAMPLE_RATES.iter().map(|sample_rate| html! {
+                <option value={sample_rate.to_string()} selected={requested == Some(*sample_rate)}>{format!("{} Hz", sample_rate)}</option>
+            }).collect::<Html>()}
+        </select>
+        </label>
+        if let Some(sample_rate) = props.sample_rate {
+            <span>{format!(" running at {} Hz", sample_rate)}</span>
+        }
+        </div>
+    };
+}
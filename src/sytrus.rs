@@ -1,101 +1,100 @@
+use web_sys::HtmlInputElement;
 use yew::{Callback, Html};
 use yew::functional::*;
 use yew::prelude::*;
+use crate::audio::params::{MAX_SYTRUS_FRAMES, SytrusParams};
 use crate::handle::{Handle, HandleChangeEvent};
-use crate::{log, waves};
+use crate::midi_learn::use_midi_learn;
+use crate::log;
 
 #[derive(Properties, PartialEq)]
 pub struct SytrusProps {
-    pub on_wave_table_change: Callback<Vec<f32>>,
-    pub wave_table_size: usize,
+    /// called whenever one of the parameters changes
+    pub on_change: Callback<SytrusParams>,
     pub mouse_down: bool,
 }
 
-
+/// Lets the Sytrus shape parameters be chosen. With more than one frame, the frames sweep every
+/// parameter from its handle in the first row to its end handle in the second row.
 #[function_component(Sytrus)]
 pub fn sytrus(props: &SytrusProps) -> Html {
-    let shape = use_state(|| 0.0 as f32);
+    let params = use_state(SytrusParams::default);
 
-    let on_shape_change = {
-        let shape_ref = shape.clone();
+    let on_handle_change = |apply: fn(&mut SytrusParams, f32)| {
+        let params_ref = params.clone();
 
         Callback::from(move |event: HandleChangeEvent| {
-            shape_ref.set(event.x);
+            let mut params = *params_ref;
+            apply(&mut params, event.x);
+            params_ref.set(params);
         })
     };
 
-    let tension = use_state(|| 0.0 as f32);
-
-    let on_tension_change = {
-        let tension_ref = tension.clone();
+    let set_frames = {
+        let params_ref = params.clone();
 
-        Callback::from(move |event: HandleChangeEvent| {
-            tension_ref.set(event.x);
+        Callback::from(move |frames: usize| {
+            params_ref.set(SytrusParams { frames: frames.clamp(1, MAX_SYTRUS_FRAMES), ..*params_ref });
         })
     };
 
-    let skew = use_state(|| 0.0 as f32);
+    let frames_learn = {
+        let set_frames = set_frames.clone();
 
-    let on_skew_change = {
-        let skew_ref = skew.clone();
-
-        Callback::from(move |event: HandleChangeEvent| {
-            skew_ref.set(event.x);
-        })
+        use_midi_learn(Some("sytrus-frames".to_string()), Callback::from(move |x: f32| {
+            set_frames.emit((1.0 + (x + 1.0) * 0.5 * (MAX_SYTRUS_FRAMES - 1) as f32).round() as usize);
+        }))
     };
 
-    let sine_shaper = use_state(|| 0.0 as f32);
+    let on_frames_change = {
+        let learning = frames_learn.learning;
 
-    let on_sine_shaper_change = {
-        let sine_shaper_ref = sine_shaper.clone();
+        Callback::from(move |event: Event| {
+            if learning {
+                return;
+            }
 
-        Callback::from(move |event: HandleChangeEvent| {
-            sine_shaper_ref.set(event.x);
-        })
-    };
-
-    let pre_filter = use_state(|| 0.0 as f32);
-
-    let on_pre_filter_change = {
-        let pre_filter_ref = pre_filter.clone();
-
-        Callback::from(move |event: HandleChangeEvent| {
-            pre_filter_ref.set(event.x);
+            let input: HtmlInputElement = event.target_unchecked_into();
+            if let Ok(value) = input.value().parse::<usize>() {
+                set_frames.emit(value);
+            }
         })
     };
 
     {
-        let shape_ref = shape.clone();
-        let tension_ref = tension.clone();
-        let skew_ref = skew.clone();
-        let sine_shaper_ref = sine_shaper.clone();
-        let pre_filter_ref = pre_filter.clone();
-
-        let on_change_ref = props.on_wave_table_change.clone();
-        let size = props.wave_table_size;
+        let params_ref = params.clone();
+        let on_change_ref = props.on_change.clone();
 
         use_effect_with_deps(move |_| {
             log!("shape changed!");
-            let shape = *shape_ref;
-            let tension = *tension_ref;
-            let skew = *skew_ref;
-            let sine_shaper = *sine_shaper_ref;
-            let pre_filter = *pre_filter_ref;
-
-            let wave_table = waves::wave_table_from_sytrus_params(shape, tension, skew, sine_shaper, pre_filter, size);
-
-            on_change_ref.emit(wave_table);
-        }, (shape.clone(), tension.clone(), skew.clone(), sine_shaper.clone(), pre_filter.clone(), props.wave_table_size));
+            on_change_ref.emit(*params_ref);
+        }, params.clone());
     }
 
     return html!{
+        <>
         <div class={"sytrus"}>
-        <Handle x={*shape} i={0} mouse_down={props.mouse_down} onchange={on_shape_change}/>
-        <Handle x={*tension} i={1} mouse_down={props.mouse_down} onchange={on_tension_change}/>
-        <Handle x={*skew} i={2} mouse_down={props.mouse_down} onchange={on_skew_change}/>
-        <Handle x={*sine_shaper} i={3} mouse_down={props.mouse_down} onchange={on_sine_shaper_change}/>
-        <Handle x={*pre_filter} i={4} mouse_down={props.mouse_down} onchange={on_pre_filter_change}/>
+        <Handle x={params.shape} i={0} mouse_down={props.mouse_down} onchange={on_handle_change(|params, x| params.shape = x)} learn_id={"sytrus-shape"}/>
+        <Handle x={params.tension} i={1} mouse_down={props.mouse_down} onchange={on_handle_change(|params, x| params.tension = x)} learn_id={"sytrus-tension"}/>
+        <Handle x={params.skew} i={2} mouse_down={props.mouse_down} onchange={on_handle_change(|params, x| params.skew = x)} learn_id={"sytrus-skew"}/>
+        <Handle x={params.sine_shaper} i={3} mouse_down={props.mouse_down} onchange={on_handle_change(|params, x| params.sine_shaper = x)} learn_id={"sytrus-sine-shaper"}/>
+        <Handle x={params.pre_filter} i={4} mouse_down={props.mouse_down} onchange={on_handle_change(|params, x| params.pre_filter = x)} learn_id={"sytrus-pre-filter"}/>
+        </div>
+        if params.frame_count() > 1 {
+            <div class={"sytrus"}>
+            <Handle x={params.shape_end} i={5} mouse_down={props.mouse_down} onchange={on_handle_change(|params, x| params.shape_end = x)} learn_id={"sytrus-shape-end"}/>
+            <Handle x={params.tension_end} i={6} mouse_down={props.mouse_down} onchange={on_handle_change(|params, x| params.tension_end = x)} learn_id={"sytrus-tension-end"}/>
+            <Handle x={params.skew_end} i={7} mouse_down={props.mouse_down} onchange={on_handle_change(|params, x| params.skew_end = x)} learn_id={"sytrus-skew-end"}/>
+            <Handle x={params.sine_shaper_end} i={8} mouse_down={props.mouse_down} onchange={on_handle_change(|params, x| params.sine_shaper_end = x)} learn_id={"sytrus-sine-shaper-end"}/>
+            <Handle x={params.pre_filter_end} i={9} mouse_down={props.mouse_down} onchange={on_handle_change(|params, x| params.pre_filter_end = x)} learn_id={"sytrus-pre-filter-end"}/>
+            </div>
+        }
+        <div class={"sytrus-frames"}>
+        <label class={classes!(frames_learn.class)} onclick={frames_learn.onclick}>{"Frames "}
+        <input type="number" min="1" max={MAX_SYTRUS_FRAMES.to_string()} value={params.frames.to_string()} onchange={on_frames_change} />
+        </label>
         </div>
+        </>
 
     }
 }
@@ -0,0 +1,130 @@
+use gloo::file::callbacks::{read_as_text, FileReader};
+use gloo::file::File;
+use web_sys::HtmlInputElement;
+use yew::{Callback, Html};
+use yew::functional::*;
+use yew::prelude::*;
+
+use dsp::scala::{self, KeyboardMapping, Scale};
+
+#[derive(Properties, PartialEq)]
+pub struct TuningViewProps {
+    /// called with the frequency of every MIDI key whenever the tuning changes
+    pub on_tuning_change: Callback<Vec<f32>>,
+}
+
+/// Loads Scala scale (.scl) and keyboard mapping (.kbm) files and sets the reference pitch.
+#[function_component(TuningView)]
+pub fn tuning_view(props: &TuningViewProps) -> Html {
+    let scale = use_state(Scale::equal_temperament);
+    let mapping = use_state(|| None as Option<KeyboardMapping>);
+    let reference_frequency = use_state(|| 440.0f64);
+    let error = use_state(|| None as Option<String>);
+
+    // the reader has to be kept alive until the file is read
+    let reader = use_mut_ref(|| None as Option<FileReader>);
+
+    {
+        let on_tuning_change = props.on_tuning_change.clone();
+        let error_handle = error.clone();
+
+        use_effect_with_deps(move |(scale, mapping, reference_frequency)| {
+            let mapping = KeyboardMapping {
+                reference_frequency: **reference_frequency,
+                ..(**mapping).clone().unwrap_or(KeyboardMapping::standard(440.0))
+            };
+
+            match scala::frequencies(scale, &mapping) {
+                Ok(frequencies) => on_tuning_change.emit(frequencies),
+                Err(err) => error_handle.set(Some(err)),
+            }
+        }, (scale.clone(), mapping.clone(), reference_frequency.clone()));
+    }
+
+    let on_file = |parse: fn(&str, &UseStateHandle<Scale>, &UseStateHandle<Option<KeyboardMapping>>, &UseStateHandle<f64>) -> Result<(), String>| {
+        let scale = scale.clone();
+        let mapping = mapping.clone();
+        let reference_frequency = reference_frequency.clone();
+        let error = error.clone();
+        let reader = reader.clone();
+
+        Callback::from(move |event: Event| {
+            let input: HtmlInputElement = event.target_unchecked_into();
+            let file = match input.files().and_then(|files| files.get(0)) {
+                Some(file) => File::from(file),
+                None => return,
+            };
+
+            let scale = scale.clone();
+            let mapping = mapping.clone();
+            let reference_frequency = reference_frequency.clone();
+            let error = error.clone();
+
+            *reader.borrow_mut() = Some(read_as_text(&file, move |text| {
+                let result = text
+                    .map_err(|err| err.to_string())
+                    .and_then(|text| parse(&text, &scale, &mapping, &reference_frequency));
+                error.set(result.err());
+            }));
+        })
+    };
+
+    let on_scl = on_file(|text, scale, _, _| {
+        scale.set(scala::parse_scl(text)?);
+        return Ok(());
+    });
+
+    let on_kbm = on_file(|text, _, mapping, reference_frequency| {
+        let kbm = scala::parse_kbm(text)?;
+        reference_frequency.set(kbm.reference_frequency);
+        mapping.set(Some(kbm));
+        return Ok(());
+    });
+
+    let on_reference_change = {
+        let reference_frequency = reference_frequency.clone();
+
+        Callback::from(move |event: Event| {
+            let input: HtmlInputElement = event.target_unchecked_into();
+            if let Ok(value) = input.value().parse::<f64>() {
+                if value > 0.0 {
+                    reference_frequency.set(value);
+                }
+            }
+        })
+    };
+
+    let on_reset = {
+        let scale = scale.clone();
+        let mapping = mapping.clone();
+        let reference_frequency = reference_frequency.clone();
+        let error = error.clone();
+
+        Callback::from(move |_| {
+            scale.set(Scale::equal_temperament());
+            mapping.set(None);
+            reference_frequency.set(440.0);
+            error.set(None);
+        })
+    };
+
+    let reference_key = mapping.as_ref().map_or(69, |mapping| mapping.reference_key);
+
+    return html! {
+        <div class={"tuning"}>
+        <h3>{"Tuning"}</h3>
+        <p>{&scale.description}{format!(" ({} notes)", scale.ratios.len())}</p>
+        <label>{"Scale (.scl) "}<input type="file" accept=".scl" onchange={on_scl} /></label>
+        <label>{"Mapping (.kbm) "}<input type="file" accept=".kbm" onchange={on_kbm} /></label>
+        <label>
+        {format!("Key {} at ", reference_key)}
+        <input type="number" min="1" step="0.01" value={reference_frequency.to_string()} onchange={on_reference_change} />
+        {" hz"}
+        </label>
+        <button onclick={on_reset}>{"Reset to 12-TET"}</button>
+        if let Some(error) = &*error {
+            <p class={"error"}>{error}</p>
+        }
+        </div>
+    };
+}
@@ -0,0 +1,75 @@
+use web_sys::HtmlInputElement;
+use yew::{Callback, Html};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::audio::params::{Curve, MAX_UNISON, SynthParam};
+use crate::learn_select::LearnSelect;
+use crate::midi_learn::use_midi_learn;
+use crate::param_slider::ParamSlider;
+
+#[derive(Properties, PartialEq)]
+pub struct UnisonSettingsProps {
+    pub on_param_change: Callback<(SynthParam, f32)>,
+}
+
+/// Lets the number of unison oscillators, their detune, stereo width and start phases be chosen.
+#[function_component(UnisonSettings)]
+pub fn unison_settings(props: &UnisonSettingsProps) -> Html {
+    let voices = use_state(|| 1usize);
+
+    let set_voices = {
+        let voices = voices.clone();
+        let on_param_change = props.on_param_change.clone();
+
+        Callback::from(move |value: usize| {
+            let value = value.clamp(1, MAX_UNISON);
+            voices.set(value);
+            on_param_change.emit((SynthParam::UnisonVoices, value as f32));
+        })
+    };
+
+    let voices_learn = {
+        let set_voices = set_voices.clone();
+
+        use_midi_learn(Some("unison-voices".to_string()), Callback::from(move |x: f32| {
+            set_voices.emit((1.0 + (x + 1.0) * 0.5 * (MAX_UNISON - 1) as f32).round() as usize);
+        }))
+    };
+
+    let on_voices_change = {
+        let learning = voices_learn.learning;
+
+        Callback::from(move |event: InputEvent| {
+            if learning {
+                return;
+            }
+
+            let input: HtmlInputElement = event.target_unchecked_into();
+            if let Ok(value) = input.value().parse::<usize>() {
+                set_voices.emit(value);
+            }
+        })
+    };
+
+    let on_curve_change = {
+        let on_param_change = props.on_param_change.clone();
+
+        Callback::from(move |index: usize| {
+            on_param_change.emit((SynthParam::UnisonDetuneCurve, index as f32));
+        })
+    };
+
+    return html! {
+        <div class={"unison-settings"}>
+        <label class={classes!(voices_learn.class)} onclick={voices_learn.onclick}>{format!("Unison {} ", *voices)}
+        <input type="range" min="1" max={MAX_UNISON.to_string()} step="1" value={voices.to_string()} oninput={on_voices_change} />
+        </label>
+        <ParamSlider label="Detune " param={SynthParam::UnisonDetune} min={0.0} max={1.0} value={0.2} learn_id="unison-detune" on_param_change={props.on_param_change.clone()} />
+        <LearnSelect options={Curve::ALL.iter().map(|curve| curve.name()).collect::<Vec<&str>>()}
+            learn_id="unison-detune-curve" on_select={on_curve_change} />
+        <ParamSlider label="Width " param={SynthParam::UnisonWidth} min={0.0} max={1.0} value={0.5} learn_id="unison-width" on_param_change={props.on_param_change.clone()} />
+        <ParamSlider label="Random Phase " param={SynthParam::UnisonRandomPhase} min={0.0} max={1.0} value={1.0} learn_id="unison-random-phase" on_param_change={props.on_param_change.clone()} />
+        </div>
+    };
+}
@@ -0,0 +1,70 @@
+use yew::{Callback, Html};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::audio::params::{SynthParam, CUSTOM_CURVE_POINTS, VelocityCurve};
+use crate::handle::{Handle, HandleChangeEvent};
+use crate::learn_select::LearnSelect;
+use crate::param_slider::ParamSlider;
+
+#[derive(Properties, PartialEq)]
+pub struct VelocitySettingsProps {
+    pub on_param_change: Callback<(SynthParam, f32)>,
+    /// called with the points of the custom curve whenever one is dragged
+    pub on_custom_curve_change: Callback<Vec<f32>>,
+    pub mouse_down: bool,
+}
+
+/// Lets the velocity curve, sensitivity and routing be chosen, and the custom curve be drawn.
+#[function_component(VelocitySettings)]
+pub fn velocity_settings(props: &VelocitySettingsProps) -> Html {
+    let curve = use_state(|| VelocityCurve::Linear);
+    let custom_curve = use_state(|| {
+        (0..CUSTOM_CURVE_POINTS)
+            .map(|i| i as f32 / (CUSTOM_CURVE_POINTS - 1) as f32)
+            .collect::<Vec<f32>>()
+    });
+
+    let on_curve_change = {
+        let curve = curve.clone();
+        let on_param_change = props.on_param_change.clone();
+
+        Callback::from(move |index: usize| {
+            if let Some(value) = VelocityCurve::from_u8(index as u8) {
+                curve.set(value);
+                on_param_change.emit((SynthParam::VelocityCurve, value as u8 as f32));
+            }
+        })
+    };
+
+    let on_point_change = {
+        let custom_curve = custom_curve.clone();
+        let on_custom_curve_change = props.on_custom_curve_change.clone();
+
+        Callback::from(move |event: HandleChangeEvent| {
+            let mut points = (*custom_curve).clone();
+            points[event.i] = event.x.clamp(0.0, 1.0);
+            on_custom_curve_change.emit(points.clone());
+            custom_curve.set(points);
+        })
+    };
+
+    return html! {
+        <div class={"velocity-settings"}>
+        <label>{"Velocity Curve "}
+        <LearnSelect options={VelocityCurve::ALL.iter().map(|velocity_curve| velocity_curve.name()).collect::<Vec<&str>>()}
+            selected={*curve as usize} learn_id="velocity-curve" on_select={on_curve_change} />
+        </label>
+        <ParamSlider label="Sensitivity " param={SynthParam::VelocitySensitivity} min={0.0} max={1.0} value={1.0} learn_id="velocity-sensitivity" on_param_change={props.on_param_change.clone()} />
+        <ParamSlider label="To Brightness " param={SynthParam::VelocityToBrightness} min={0.0} max={1.0} value={0.0} learn_id="velocity-to-brightness" on_param_change={props.on_param_change.clone()} />
+        <ParamSlider label="To Attack " param={SynthParam::VelocityToAttack} min={0.0} max={1.0} value={0.0} learn_id="velocity-to-attack" on_param_change={props.on_param_change.clone()} />
+        if *curve == VelocityCurve::Custom {
+            <div class={"velocity-curve"}>
+            {custom_curve.iter().enumerate().map(|(i, x)| html! {
+                <Handle x={*x} i={i} onchange={on_point_change.clone()} mouse_down={props.mouse_down} learn_id={format!("velocity-curve-{}", i)} />
+            }).collect::<Html>()}
+            </div>
+        }
+        </div>
+    };
+}
@@ -0,0 +1,137 @@
+use web_sys::HtmlInputElement;
+use yew::{Callback, Html};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::audio::params::{DEFAULT_MAX_VOICES, DEFAULT_PITCH_BEND_RANGE, DEFAULT_STEAL_POLICY, GlideMode, MAX_VOICES, NotePriority, StealPolicy, SynthParam, VoiceMode};
+use crate::learn_select::LearnSelect;
+use crate::midi_learn::use_midi_learn;
+use crate::param_slider::ParamSlider;
+
+/// The longest glide time that can be set, in seconds.
+const MAX_GLIDE_TIME: f32 = 5.0;
+
+#[derive(Properties, PartialEq)]
+pub struct VoiceSettingsProps {
+    pub on_param_change: Callback<(SynthParam, f32)>,
+    /// called with the voice limit and the steal policy whenever either changes
+    pub on_polyphony_change: Callback<(u32, StealPolicy)>,
+}
+
+/// Lets the voice mode, note priority, voice limit, glide, pitch bend range and stereo placement be
+/// chosen.
+#[function_component(VoiceSettings)]
+pub fn voice_settings(props: &VoiceSettingsProps) -> Html {
+    let on_select = |param: SynthParam| {
+        let on_param_change = props.on_param_change.clone();
+
+        Callback::from(move |index: usize| {
+            on_param_change.emit((param, index as f32));
+        })
+    };
+
+    let max_voices = use_state(|| DEFAULT_MAX_VOICES as u32);
+    let steal_policy = use_state(|| DEFAULT_STEAL_POLICY);
+    let glide_time = use_state(|| 0.0f32);
+
+    let set_max_voices = {
+        let max_voices = max_voices.clone();
+        let steal_policy = steal_policy.clone();
+        let on_polyphony_change = props.on_polyphony_change.clone();
+
+        Callback::from(move |value: u32| {
+            let value = value.clamp(1, MAX_VOICES as u32);
+            max_voices.set(value);
+            on_polyphony_change.emit((value, *steal_policy));
+        })
+    };
+
+    let max_voices_learn = {
+        let set_max_voices = set_max_voices.clone();
+
+        use_midi_learn(Some("max-voices".to_string()), Callback::from(move |x: f32| {
+            set_max_voices.emit((1.0 + (x + 1.0) * 0.5 * (MAX_VOICES - 1) as f32).round() as u32);
+        }))
+    };
+
+    let on_max_voices_change = Callback::from(move |event: Event| {
+        let input: HtmlInputElement = event.target_unchecked_into();
+        if let Ok(value) = input.value().parse::<u32>() {
+            set_max_voices.emit(value);
+        }
+    });
+
+    let on_steal_policy_change = {
+        let max_voices = max_voices.clone();
+        let steal_policy = steal_policy.clone();
+        let on_polyphony_change = props.on_polyphony_change.clone();
+
+        Callback::from(move |index: usize| {
+            if let Some(policy) = StealPolicy::from_u8(index as u8) {
+                steal_policy.set(policy);
+                on_polyphony_change.emit((*max_voices, policy));
+            }
+        })
+    };
+
+    let set_glide_time = {
+        let glide_time = glide_time.clone();
+        let on_param_change = props.on_param_change.clone();
+
+        Callback::from(move |value: f32| {
+            let value = value.clamp(0.0, MAX_GLIDE_TIME);
+            glide_time.set(value);
+            on_param_change.emit((SynthParam::GlideTime, value));
+        })
+    };
+
+    let glide_time_learn = {
+        let set_glide_time = set_glide_time.clone();
+
+        use_midi_learn(Some("glide-time".to_string()), Callback::from(move |x: f32| {
+            set_glide_time.emit((x + 1.0) * 0.5 * MAX_GLIDE_TIME);
+        }))
+    };
+
+    let on_glide_time_change = Callback::from(move |event: Event| {
+        let input: HtmlInputElement = event.target_unchecked_into();
+        if let Ok(value) = input.value().parse::<f32>() {
+            set_glide_time.emit(value);
+        }
+    });
+
+    return html! {
+        <div class={"voice-settings"}>
+        <label>{"Voice Mode "}
+        <LearnSelect options={VoiceMode::ALL.iter().map(|mode| mode.name()).collect::<Vec<&str>>()}
+            learn_id="voice-mode" on_select={on_select(SynthParam::VoiceMode)} />
+        </label>
+        <label>{"Priority "}
+        <LearnSelect options={NotePriority::ALL.iter().map(|priority| priority.name()).collect::<Vec<&str>>()}
+            learn_id="note-priority" on_select={on_select(SynthParam::NotePriority)} />
+        </label>
+        <label class={classes!(max_voices_learn.class)} onclick={max_voices_learn.onclick}>{"Voices "}
+        <input type="number" min="1" max={MAX_VOICES.to_string()} step="1" value={max_voices.to_string()} onchange={on_max_voices_change} />
+        </label>
+        <label>{"Steal "}
+        <LearnSelect options={StealPolicy::ALL.iter().map(|policy| policy.name()).collect::<Vec<&str>>()}
+            selected={*steal_policy as usize} learn_id="steal-policy" on_select={on_steal_policy_change} />
+        </label>
+        <label class={classes!(glide_time_learn.class)} onclick={glide_time_learn.onclick}>{"Glide "}
+        <input type="number" min="0" max={MAX_GLIDE_TIME.to_string()} step="0.01" value={glide_time.to_string()} onchange={on_glide_time_change} />
+        {" s"}
+        </label>
+        <LearnSelect options={GlideMode::ALL.iter().map(|mode| mode.name()).collect::<Vec<&str>>()}
+            learn_id="glide-mode" on_select={on_select(SynthParam::GlideMode)} />
+        <div>
+        <ParamSlider label="Bend Range " param={SynthParam::PitchBendRange} min={0.0} max={24.0} value={DEFAULT_PITCH_BEND_RANGE}
+            learn_id="pitch-bend-range" on_param_change={props.on_param_change.clone()} />
+        </div>
+        <div>
+        <ParamSlider label="Pan " param={SynthParam::Pan} min={-1.0} max={1.0} value={0.0} learn_id="pan" on_param_change={props.on_param_change.clone()} />
+        <ParamSlider label="Key Spread " param={SynthParam::PanSpread} min={-1.0} max={1.0} value={0.0} learn_id="pan-spread" on_param_change={props.on_param_change.clone()} />
+        <ParamSlider label="Balance " param={SynthParam::Balance} min={-1.0} max={1.0} value={0.0} learn_id="balance" on_param_change={props.on_param_change.clone()} />
+        </div>
+        </div>
+    };
+}
@@ -0,0 +1,26 @@
+use yew::{Callback, Html};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::audio::params::SynthParam;
+use crate::param_slider::ParamSlider;
+
+#[derive(Properties, PartialEq)]
+pub struct WavePositionSettingsProps {
+    pub on_param_change: Callback<(SynthParam, f32)>,
+}
+
+/// Lets the position within a multi frame wave table be chosen, and how far the envelope, an lfo
+/// and the velocity move it.
+#[function_component(WavePositionSettings)]
+pub fn wave_position_settings(props: &WavePositionSettingsProps) -> Html {
+    return html! {
+        <div class={"wave-position"}>
+        <ParamSlider label="Position " param={SynthParam::WavePosition} min={0.0} max={1.0} value={0.0} learn_id="wave-position" on_param_change={props.on_param_change.clone()} />
+        <ParamSlider label="Envelope " param={SynthParam::WavePositionEnvelope} min={-1.0} max={1.0} value={0.0} learn_id="wave-position-envelope" on_param_change={props.on_param_change.clone()} />
+        <ParamSlider label="LFO Depth " param={SynthParam::WavePositionLfoDepth} min={0.0} max={1.0} value={0.0} learn_id="wave-position-lfo-depth" on_param_change={props.on_param_change.clone()} />
+        <ParamSlider label="LFO Rate " param={SynthParam::WavePositionLfoRate} min={0.0} max={20.0} value={1.0} learn_id="wave-position-lfo-rate" on_param_change={props.on_param_change.clone()} />
+        <ParamSlider label="Velocity " param={SynthParam::WavePositionVelocity} min={-1.0} max={1.0} value={0.0} learn_id="wave-position-velocity" on_param_change={props.on_param_change.clone()} />
+        </div>
+    };
+}
@@ -14,7 +14,13 @@ class MasterProcessor extends AudioWorkletProcessor {
             case "midi":
                 console.log("processor got midi message", event.data)
                 const msg = event.data;
-                worker.on_midi(msg.is_active, msg.note, msg.velocity);
+                worker.on_midi(msg.is_active, msg.channel, msg.note, msg.velocity, msg.time ?? 0);
+                break;
+            case "pitchBend":
+                worker.on_pitch_bend(event.data.channel, event.data.value, event.data.time ?? 0);
+                break;
+            case "controlChange":
+                worker.on_control_change(event.data.channel, event.data.controller, event.data.value, event.data.time ?? 0);
                 break;
             case "wasmModule":
                 const module = event.data.value;
@@ -24,23 +30,42 @@ class MasterProcessor extends AudioWorkletProcessor {
             case "waveTable":
                 const waveTable = event.data.value;
                 console.log("wave table:", waveTable);
-                worker.set_wave_table(waveTable);
+                worker.set_wave_table(waveTable.samples, waveTable.frame_size);
                 console.log("set wave table")
                 break;
+            case "envelope":
+                worker.set_envelope(event.data.value);
+                break;
+            case "velocityCurve":
+                worker.set_velocity_curve(event.data.value);
+                break;
+            case "tuning":
+                worker.set_tuning(event.data.value);
+                break;
+            case "ccMapping":
+                const mapping = event.data.value;
+                worker.set_cc_mapping(mapping.channel, mapping.controller, mapping.param, mapping.min, mapping.max, mapping.curve);
+                break;
+            case "removeCcMapping":
+                worker.remove_cc_mapping(event.data.value);
+                break;
+            case "sampleRate":
+                worker.set_sample_rate(event.data.value);
+                break;
+            case "polyphony":
+                const polyphony = event.data.value;
+                worker.set_polyphony(polyphony.max_voices, polyphony.steal_policy);
+                break;
+            case "param":
+                const param = event.data.value;
+                worker.set_param(param.param, param.value);
+                break;
         }
     }
 
     process(inputs, outputs) {
         const output = outputs[0];
-        const currentSamples = worker.calculate_samples(output[0].length);
-
-        for (let channel = 0; channel < output.length; ++channel) {
-            const outputChannel = output[channel];
-
-            for (let i = 0; i < outputChannel.length; ++i) {
-                outputChannel[i] = currentSamples[i];
-            }
-        }
+        worker.calculate_samples(output[0], output[1], currentFrame);
 
         return true;
     }